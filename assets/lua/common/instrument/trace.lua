@@ -0,0 +1,84 @@
+-- runs FN once with expression nodes instead of numbers.
+-- the returned trees are compiled into a native program, see `dsp/expression.rs`.
+-- anything that can't be traced raises an error and the wave stays interpreted.
+
+local EXPR = {}
+
+local function expr(op, a, b)
+    return setmetatable({ op = op, a = a, b = b }, EXPR)
+end
+
+local function is_expr(v)
+    return getmetatable(v) == EXPR
+end
+
+local function untraceable()
+    error("wave can not be traced")
+end
+
+EXPR.__add = function(a, b) return expr("add", a, b) end
+EXPR.__sub = function(a, b) return expr("sub", a, b) end
+EXPR.__mul = function(a, b) return expr("mul", a, b) end
+EXPR.__div = function(a, b) return expr("div", a, b) end
+EXPR.__mod = function(a, b) return expr("mod", a, b) end
+EXPR.__pow = function(a, b) return expr("pow", a, b) end
+EXPR.__unm = function(a) return expr("neg", a) end
+-- comparing two signals, lua compares a signal with a plain number without asking
+-- and always finds them unequal, so `==` against a number can not be caught here.
+EXPR.__eq = untraceable
+EXPR.__lt = untraceable
+EXPR.__le = untraceable
+EXPR.__concat = untraceable
+
+local UNARY = { "sin", "cos", "abs", "sqrt", "exp" }
+
+TRACE_FN = function()
+    local math_fns = {}
+    local waves = {}
+
+    for _, name in ipairs(UNARY) do
+        local f = math[name]
+        local traced = function(x)
+            if is_expr(x) then
+                return expr(name, x)
+            end
+            return f(x)
+        end
+
+        math_fns[name] = f
+        waves[f] = name
+        waves[traced] = name
+        math[name] = traced
+    end
+
+    local w, gp, t = W, GP, T
+
+    W_I = 0
+    W = function(wave_fn, f)
+        local wave = waves[wave_fn]
+        if wave == nil then
+            untraceable()
+        end
+
+        W_I = W_I + 1
+
+        local osc = expr("osc", wave, f)
+        osc.slot = W_I
+        return osc
+    end
+    GP = untraceable
+    T = expr("time")
+
+    local ok, l, r = pcall(FN, expr("frequency"))
+
+    for name, f in pairs(math_fns) do
+        math[name] = f
+    end
+    W, GP, T = w, gp, t
+
+    if not ok then
+        error(l)
+    end
+
+    return l, r
+end
@@ -1,17 +1,26 @@
 TAU = math.pi * 2.0
 
+-- one phase per W call of FN, advanced every sample.
 P = {}
 W_I = 0
 
 T = 0.0
 TS = 0.0
 
-OUT_FN = function(t, frequency, t_size)
-    W_I = 0
-    T = t
-    TS = t_size
+-- filled by OUT_BLOCK_FN, read back after every block.
+OUT_L = {}
+OUT_R = {}
 
-    return FN(frequency)
+-- t0 is the time of the first sample of the block in seconds, T the time of the current one.
+OUT_BLOCK_FN = function(n, sample_rate, t0, frequency)
+    TS = 1.0 / sample_rate
+
+    for i = 1, n do
+        W_I = 0
+        T = t0 + (i - 1) * TS
+
+        OUT_L[i], OUT_R[i] = FN(frequency)
+    end
 end
 
 GP = function(f)
@@ -30,7 +39,5 @@ end
 W = function(wave_fn, f)
     W_I = W_I + 1
 
-    local phase = GP(f)
-    local res = wave_fn(T * f + phase)
-    return res
+    return wave_fn(GP(f))
 end
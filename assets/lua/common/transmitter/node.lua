@@ -2,7 +2,7 @@ node.active.foreground = SAPPHIRE
 node.type = { NODE_TYPES.Receiver, NODE_TYPES.SignalConst }
 node.slots = {
     {
-        signal_type = NODE_TYPES.SignalLink,
+        signal_type = NODE_TYPES.SignalConst,
         slot_type = SLOT_TYPE.F32x2,
         pos = { x = 0, y = 1 },
         direction = { x = 0, y = 0 },
@@ -8,6 +8,36 @@ node.slots = {
         pos = { x = -1, y = 0 },
         direction = { x = 0, y = 0 },
     },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 1, y = 0 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = -1, y = 1 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 1, y = 1 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = -2, y = 0 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 2, y = 0 },
+        direction = { x = 0, y = 0 },
+    },
 }
 node.output_slots = {
     {
@@ -17,3 +47,6 @@ node.output_slots = {
         direction = { x = 0, y = -1 },
     },
 }
+-- bang, frequency (Hz), voices (0 runs freely, up to 8), steal mode (0 oldest, 1 quietest),
+-- attack, release (seconds)
+data.slot_data = { false, 80.0, 0.0, 0.0, 0.005, 0.4 }
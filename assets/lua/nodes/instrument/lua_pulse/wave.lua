@@ -1,5 +1,5 @@
-FN = function(_) -- frequency
-    local f = 80.0 * TAU
+FN = function(frequency) -- radians per second, from the frequency slot
+    local f = frequency
 
     local lwave = W(math.sin, f)
     lwave = (lwave * 0.5) + (W(math.cos, f / 1.0) * 0.5)
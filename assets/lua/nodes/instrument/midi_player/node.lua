@@ -0,0 +1,27 @@
+node.display = "P"
+node.name = "midi_player"
+node.ntype = { NODE_TYPES.Emitter }
+node.asset = "midi/arp.mid"
+node.slots = {}
+node.output_slots = {
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.Bang,
+        pos = { x = 0, y = -1 },
+        direction = { x = 0, y = -1 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = -1, y = -1 },
+        direction = { x = 0, y = -1 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 1, y = -1 },
+        direction = { x = 0, y = -1 },
+    },
+}
+-- outputs: bang, notes (note ons and offs of the tick), velocity (0.0 - 1.0)
+data.slot_data = {}
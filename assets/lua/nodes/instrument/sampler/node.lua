@@ -0,0 +1,46 @@
+node.display = "S"
+node.name = "sampler"
+node.ntype = { NODE_TYPES.Receiver, NODE_TYPES.Emitter }
+node.asset = "samples/kick.wav"
+node.slots = {
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.Bang,
+        pos = { x = -1, y = 0 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 1, y = 1 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 1, y = 0 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = -1, y = 1 },
+        direction = { x = 0, y = 0 },
+    },
+    {
+        signal_type = NODE_TYPES.Signal,
+        slot_type = SLOT_TYPE.F32,
+        pos = { x = 0, y = 1 },
+        direction = { x = 0, y = 0 },
+    },
+}
+node.output_slots = {
+    {
+        signal_type = NODE_TYPES.SignalLink,
+        slot_type = SLOT_TYPE.F32x2,
+        pos = { x = 0, y = -1 },
+        direction = { x = 0, y = -1 },
+    },
+}
+-- bang, start, end, pitch (semitones), loop mode (0 off, 1 forward, 2 ping pong)
+data.slot_data = { false, 0.0, 1.0, 0.0, 0.0 }
@@ -0,0 +1,25 @@
+-- frames of the table, run once when the chain is built.
+-- every frame is a single cycle, the position slot blends from the first to the last.
+local FRAMES = 8
+local SIZE = 256
+
+WAVETABLE = {}
+
+for f = 1, FRAMES do
+    -- more harmonics each frame, from a sine to a band limited saw.
+    local harmonics = 2 ^ (f - 1)
+    local frame = {}
+
+    for i = 1, SIZE do
+        local phase = (i - 1) / SIZE
+        local sample = 0
+
+        for h = 1, harmonics do
+            sample = sample + math.sin(tau * h * phase) / h
+        end
+
+        frame[i] = sample * 0.6
+    end
+
+    WAVETABLE[f] = frame
+end
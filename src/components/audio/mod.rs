@@ -1 +1,234 @@
-pub mod system;
\ No newline at end of file
+pub mod system;
+
+use bevy::{
+    ecs::{entity::Entity, system::Resource},
+    utils::{HashMap, HashSet},
+};
+use knyst::graph::NodeId;
+
+use crate::{
+    components::nodes::types::Position,
+    dsp::{oscillators::WaveError, Dsp, TChain},
+};
+
+/// AudioGraph - the `AST` of all audio chains on the grid.
+/// Each root chain is started by an instrument, links are appended as pulses connect nodes.
+#[derive(Resource, Default)]
+pub struct AudioGraph {
+    chain: Vec<TChain>,
+    nodes: HashMap<usize, Vec<NodeId>>,
+    // the knyst node every link entity built, chains reaching it later are sent into it.
+    link_nodes: HashMap<Entity, NodeId>,
+    dirty: HashSet<usize>,
+}
+
+impl AudioGraph {
+    pub fn get_chain(&self) -> &Vec<TChain> {
+        &self.chain
+    }
+
+    pub fn get_chain_mut(&mut self) -> &mut Vec<TChain> {
+        &mut self.chain
+    }
+
+    /// finds the root chain that contains a link created by the entity.
+    pub fn chain_idx(&self, entity: Entity) -> Option<usize> {
+        self.chain.iter().position(|chain| chain.contains(entity))
+    }
+
+    /// marks a root chain to be (re)built into knyst nodes.
+    pub fn mark_dirty(&mut self, idx: usize) {
+        self.dirty.insert(idx);
+    }
+
+    pub fn take_dirty(&mut self) -> Vec<usize> {
+        self.dirty.drain().collect()
+    }
+
+    /// knyst nodes currently built for a root chain.
+    pub fn get_nodes(&self, idx: usize) -> Option<&Vec<NodeId>> {
+        self.nodes.get(&idx)
+    }
+
+    pub fn set_nodes(&mut self, idx: usize, nodes: Vec<NodeId>) -> Option<Vec<NodeId>> {
+        self.nodes.insert(idx, nodes)
+    }
+
+    pub fn remove_nodes(&mut self, idx: usize) -> Option<Vec<NodeId>> {
+        self.nodes.remove(&idx)
+    }
+
+    pub fn set_link_node(&mut self, entity: Entity, node: NodeId) {
+        self.link_nodes.insert(entity, node);
+    }
+
+    /// every root chain has its knyst nodes and none waits for a rebuild.
+    pub fn is_built(&self) -> bool {
+        self.dirty.is_empty() && (0..self.chain.len()).all(|idx| self.nodes.contains_key(&idx))
+    }
+
+    /// points the bus sends of a chain at the nodes they feed that are currently built.
+    pub fn resolve_sends(&mut self, idx: usize) {
+        let link_nodes = &self.link_nodes;
+
+        self.chain[idx]
+            .flatten_mut()
+            .into_iter()
+            .for_each(|(dsp, _)| {
+                if let Dsp::BusSend(send) = dsp {
+                    send.node = link_nodes.get(&send.bus).cloned();
+                }
+            });
+    }
+
+    /// root chains sending into the node of the entity, a mixer or a node reached by two chains.
+    pub fn bus_sources(&self, entity: Entity) -> Vec<usize> {
+        self.chain
+            .iter()
+            .enumerate()
+            .filter(|(_, chain)| {
+                chain.flatten().iter().any(|(dsp, _)| match dsp {
+                    Dsp::BusSend(send) => send.bus == entity,
+                    _ => false,
+                })
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// A lua error of the wave a node plays, kept until the wave is reloaded.
+pub struct NodeError {
+    pub entity: Entity,
+    pub node: String,
+    pub pos: Position,
+    pub error: WaveError,
+    // times the node reported the error, a steady error is only counted again
+    // once a different one of the node happened in between.
+    pub count: usize,
+    // the last error the node reported.
+    latest: bool,
+}
+
+/// Lua errors of every playing wave, one entry per node and error.
+#[derive(Resource, Default)]
+pub struct WaveErrors {
+    errors: Vec<NodeError>,
+}
+
+impl WaveErrors {
+    pub fn iter(&self) -> impl Iterator<Item = &NodeError> {
+        self.errors.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// returns false when the node already reported the same error.
+    pub fn push(&mut self, entity: Entity, node: String, pos: Position, error: WaveError) -> bool {
+        let mut known = None;
+
+        self.errors
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, listed)| listed.entity == entity)
+            .for_each(|(i, listed)| {
+                if listed.error == error {
+                    known = Some(i);
+                    if !listed.latest {
+                        listed.count += 1;
+                    }
+                }
+
+                listed.latest = listed.error == error;
+            });
+
+        if known.is_some() {
+            return false;
+        }
+
+        self.errors.push(NodeError {
+            entity,
+            node,
+            pos,
+            error,
+            count: 1,
+            latest: true,
+        });
+
+        true
+    }
+
+    /// keeps the errors of nodes the predicate holds for.
+    pub fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        self.errors.retain(|error| keep(error.entity));
+    }
+
+    pub fn clear_node(&mut self, entity: Entity) {
+        self.retain(|e| e != entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::Entity;
+
+    use crate::{components::nodes::types::Position, dsp::oscillators::WaveError};
+
+    use super::WaveErrors;
+
+    fn error(message: &str) -> WaveError {
+        WaveError {
+            file: Some("lua/nodes/instrument/lua_pulse/wave.lua".to_string()),
+            line: Some(3),
+            message: message.to_string(),
+        }
+    }
+
+    fn push(errors: &mut WaveErrors, entity: Entity, message: &str) -> bool {
+        errors.push(
+            entity,
+            "lua_pulse".to_string(),
+            Position::new(0, 0),
+            error(message),
+        )
+    }
+
+    fn count(errors: &WaveErrors, entity: Entity, message: &str) -> usize {
+        errors
+            .iter()
+            .find(|listed| listed.entity == entity && listed.error.message == message)
+            .map_or(0, |listed| listed.count)
+    }
+
+    #[test]
+    fn push_skips_duplicates() {
+        let mut errors = WaveErrors::default();
+        let node = Entity::from_raw(1);
+        let other = Entity::from_raw(2);
+
+        assert!(push(&mut errors, node, "boom"));
+        assert!(!push(&mut errors, node, "boom"));
+        assert!(!push(&mut errors, node, "boom"));
+        assert_eq!(count(&errors, node, "boom"), 1);
+
+        // the same error of another node is listed on its own.
+        assert!(push(&mut errors, other, "boom"));
+        assert_eq!(errors.iter().count(), 2);
+    }
+
+    #[test]
+    fn push_counts_returning_errors() {
+        let mut errors = WaveErrors::default();
+        let node = Entity::from_raw(1);
+
+        assert!(push(&mut errors, node, "boom"));
+        assert!(push(&mut errors, node, "bang"));
+        assert!(!push(&mut errors, node, "boom"));
+        assert!(!push(&mut errors, node, "boom"));
+
+        assert_eq!(count(&errors, node, "boom"), 2);
+        assert_eq!(count(&errors, node, "bang"), 1);
+    }
+}
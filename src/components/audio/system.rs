@@ -0,0 +1,429 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        event::EventReader,
+        query::{Added, Changed, Or},
+        system::{Commands, NonSendMut, Query, Res, ResMut},
+    },
+    log::{error, info},
+};
+use knyst::{controller::KnystCommands, graph::NodeId, knyst_commands};
+
+use crate::{
+    components::{
+        grid::Grid,
+        nodes::{
+            generic::{types::NoteEvent, GenericNode},
+            types::{NodeVarient, ParentNode, SlotData},
+        },
+        transport::Transport,
+    },
+    dsp::{
+        audio_graph::{insert_control, AudioControl, AudioOutput, DspAssets},
+        delay::{self, Delay},
+        envelope::{Envelope, DEFAULT_ATTACK, DEFAULT_DECAY, DEFAULT_RELEASE, DEFAULT_SUSTAIN},
+        filters::Filter,
+        generators::Generator,
+        mixer::{Mixer, DEFAULT_GAIN, MIXER_INPUTS},
+        oscillators::{self, Oscillator, WaveReport},
+        pan::{self, Pan},
+        realtime::RealtimeCheck,
+        reverb::{self, Reverb},
+        sampler::{self, LoopMode, Sampler},
+        voices::{self, StealMode},
+        wavetable::{self, Wavetable},
+        AudioControl as AC, Dsp,
+    },
+};
+
+use super::{AudioGraph, WaveErrors};
+
+/// Turns every dirty chain of the audio graph into knyst nodes.
+/// Previously built nodes of the chain are freed first.
+pub fn build_audio_chains(
+    mut commands: Commands,
+    mut graph: ResMut<AudioGraph>,
+    assets: DspAssets,
+    mut audio_output: NonSendMut<AudioOutput>,
+) {
+    for idx in graph.take_dirty() {
+        graph.resolve_sends(idx);
+
+        let mut streams = vec![];
+        let mut controls = vec![];
+
+        let mut loaded = true;
+
+        graph.get_chain_mut()[idx]
+            .flatten_mut()
+            .into_iter()
+            .for_each(|(dsp, entity)| match dsp.to_stream(&assets) {
+                Some(send_control) => {
+                    let (stream, control) = send_control.split();
+
+                    streams.push(stream);
+                    controls.push((entity, control));
+                }
+                None => loaded = false,
+            });
+
+        if !loaded {
+            // assets are not ready yet, retry next frame.
+            graph.mark_dirty(idx);
+            continue;
+        }
+
+        if let Some(mut nodes) = graph.remove_nodes(idx) {
+            info!("freeing audio chain: {}", idx);
+
+            // outputs share the node id of the link before them.
+            nodes.dedup();
+            nodes
+                .into_iter()
+                .for_each(|node| knyst_commands().free_node(node));
+        }
+
+        if streams.is_empty() {
+            continue;
+        }
+
+        info!("building audio chain: {}", idx);
+
+        let links = audio_output.play_stream(streams);
+        let node_addresses = links
+            .iter()
+            .flatten()
+            .map(|(node_address, _)| *node_address)
+            .collect::<Vec<NodeId>>();
+
+        for ((entity, control), link) in controls.into_iter().zip(links) {
+            if let (Some(entity), Some((node_address, stats))) = (entity, link) {
+                // a bus send is keyed by the node it feeds.
+                if !matches!(control, AC::Bus) {
+                    graph.set_link_node(entity, node_address);
+                }
+
+                insert_control(&mut commands, entity, control, node_address, stats.load);
+
+                if let Some(report) = stats.realtime {
+                    commands.entity(entity).insert(RealtimeCheck::new(report));
+                }
+            }
+        }
+
+        graph.set_nodes(idx, node_addresses);
+
+        // chains sending into a rebuilt node lost their connection with the old one.
+        let targets = graph.get_chain()[idx]
+            .flatten()
+            .into_iter()
+            .filter(|(dsp, _)| !matches!(dsp, Dsp::BusSend(_)))
+            .filter_map(|(_, entity)| entity)
+            .collect::<Vec<Entity>>();
+
+        targets
+            .into_iter()
+            .flat_map(|entity| graph.bus_sources(entity))
+            .filter(|source| *source != idx)
+            .collect::<Vec<usize>>()
+            .into_iter()
+            .for_each(|source| graph.mark_dirty(source));
+    }
+}
+
+/// Pushes the frequency slot of lua oscillators into their running streams.
+/// Pulses carrying a value write it into the slot, so the pitch can be sequenced.
+/// With voices the notes of pulses are played by `play_notes`, without them the wave runs freely.
+/// slots: 0 - bang, 1 - frequency, 2 - voices, 3 - steal mode (0 oldest, 1 quietest),
+/// 4 - attack, 5 - release (seconds)
+pub fn update_oscillators(
+    mut query: Query<
+        (&mut GenericNode, &AudioControl<Oscillator>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Oscillator>>)>,
+    >,
+) {
+    for (mut gnode, control) in query.iter_mut() {
+        let data = gnode.get_data();
+        let frequency = data.get_f32(1).unwrap_or(oscillators::DEFAULT_FREQUENCY);
+
+        control.set_frequency(frequency);
+        control
+            .voices()
+            .set_count(data.get_f32(2).unwrap_or_default().round().max(0.0) as usize);
+        control
+            .voices()
+            .set_steal(StealMode::from_f32(data.get_f32(3).unwrap_or_default()));
+        control.voices().set_envelope(
+            data.get_f32(4).unwrap_or(voices::DEFAULT_ATTACK),
+            data.get_f32(5).unwrap_or(voices::DEFAULT_RELEASE),
+        );
+
+        // a bang written by osc is consumed so the next one can trigger again.
+        if matches!(data.slot_data.first(), Some(SlotData::Bang(true))) {
+            gnode.get_data_mut().slot_data[0] = SlotData::Bang(false);
+            control.voices().trigger(frequency);
+        }
+    }
+}
+
+/// Plays the notes pulses deliver to lua oscillators on their voices.
+pub fn play_notes(
+    mut ev_note: EventReader<NoteEvent>,
+    query: Query<(&GenericNode, &AudioControl<Oscillator>)>,
+) {
+    for ev in ev_note.read() {
+        match *ev {
+            NoteEvent::Trigger { entity, frequency } => {
+                if let Ok((gnode, control)) = query.get(entity) {
+                    let frequency = frequency.unwrap_or_else(|| {
+                        gnode
+                            .get_data()
+                            .get_f32(1)
+                            .unwrap_or(oscillators::DEFAULT_FREQUENCY)
+                    });
+
+                    control.voices().trigger(frequency);
+                }
+            }
+            NoteEvent::On { entity, note } => {
+                if let Ok((_, control)) = query.get(entity) {
+                    control.voices().note_on(note.key, note.frequency());
+                }
+            }
+            NoteEvent::Off { entity, key } => {
+                if let Ok((_, control)) = query.get(entity) {
+                    control.voices().note_off(key);
+                }
+            }
+        }
+    }
+}
+
+/// Moves the lua errors of playing waves into `WaveErrors`, a reloaded wave clears the ones of its node.
+pub fn collect_wave_errors(
+    query: Query<(Entity, &GenericNode, &AudioControl<Oscillator>)>,
+    mut errors: ResMut<WaveErrors>,
+) {
+    // nodes that were removed or rebuilt into another stream.
+    errors.retain(|entity| query.contains(entity));
+
+    for (entity, gnode, control) in query.iter() {
+        for report in control.reports() {
+            match report {
+                WaveReport::Reloaded => errors.clear_node(entity),
+                WaveReport::Error(error) => {
+                    let node = gnode.get_node();
+
+                    if errors.push(entity, node.name.to_string(), node.pos, error.clone()) {
+                        error!(
+                            "lua error in {}:{} ({} at {}, {}) - {}",
+                            error.file.unwrap_or_default(),
+                            error.line.unwrap_or_default(),
+                            node.name.to_string(),
+                            node.pos.x,
+                            node.pos.y,
+                            error.message
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pushes the slot values of generator nodes into their running streams.
+/// slots: 0 - frequency, 1 - amplitude, 2 - width
+pub fn update_generators(
+    query: Query<
+        (&GenericNode, &AudioControl<Generator>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Generator>>)>,
+    >,
+) {
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        if let Some(frequency) = data.get_f32(0) {
+            control.set_frequency(frequency);
+        }
+        if let Some(amplitude) = data.get_f32(1) {
+            control.set_amplitude(amplitude);
+        }
+        if let Some(width) = data.get_f32(2) {
+            control.set_width(width);
+        }
+    }
+}
+
+/// Pushes the slot values of filter nodes into their running streams.
+/// slots: 0 - audio in, 1 - cutoff, 2 - resonance
+pub fn update_filters(
+    query: Query<
+        (&GenericNode, &AudioControl<Filter>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Filter>>)>,
+    >,
+) {
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        if let Some(cutoff) = data.get_f32(1) {
+            control.set_cutoff(cutoff);
+        }
+        if let Some(resonance) = data.get_f32(2) {
+            control.set_resonance(resonance);
+        }
+    }
+}
+
+/// Triggers envelopes on a bang and pushes their adsr slots into the stream.
+/// slots: 0 - audio in, 1 - bang, 2 - attack, 3 - decay, 4 - sustain, 5 - release
+pub fn update_envelopes(
+    mut query: Query<
+        (&mut GenericNode, &AudioControl<Envelope>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Envelope>>)>,
+    >,
+) {
+    for (mut gnode, control) in query.iter_mut() {
+        let data = gnode.get_data();
+
+        control.set_adsr(
+            data.get_f32(2).unwrap_or(DEFAULT_ATTACK),
+            data.get_f32(3).unwrap_or(DEFAULT_DECAY),
+            data.get_f32(4).unwrap_or(DEFAULT_SUSTAIN),
+            data.get_f32(5).unwrap_or(DEFAULT_RELEASE),
+        );
+
+        // the bang is consumed so the next pulse can trigger again.
+        if matches!(data.slot_data.get(1), Some(SlotData::Bang(true))) {
+            gnode.get_data_mut().slot_data[1] = SlotData::Bang(false);
+            control.trigger();
+        }
+    }
+}
+
+/// Pushes the slot values of delay nodes into their running streams.
+/// The delay time is set in ticks of the transport so it follows the tempo.
+/// slots: 0 - audio in, 1 - time in ticks, 2 - feedback, 3 - mix
+pub fn update_delays(
+    transport: Res<Transport>,
+    query: Query<(&GenericNode, &AudioControl<Delay>)>,
+) {
+    let tick = transport.tick_seconds();
+
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        control.set_time(data.get_f32(1).unwrap_or(delay::DEFAULT_TICKS) * tick);
+        control.set_feedback(data.get_f32(2).unwrap_or(delay::DEFAULT_FEEDBACK));
+        control.set_mix(data.get_f32(3).unwrap_or(delay::DEFAULT_MIX));
+    }
+}
+
+/// Pushes the slot values of reverb nodes into their running streams.
+/// slots: 0 - audio in, 1 - room, 2 - damping, 3 - mix
+pub fn update_reverbs(
+    query: Query<
+        (&GenericNode, &AudioControl<Reverb>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Reverb>>)>,
+    >,
+) {
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        control.set_room(data.get_f32(1).unwrap_or(reverb::DEFAULT_ROOM));
+        control.set_damping(data.get_f32(2).unwrap_or(reverb::DEFAULT_DAMPING));
+        control.set_mix(data.get_f32(3).unwrap_or(reverb::DEFAULT_MIX));
+    }
+}
+
+/// Pushes the gain slots of mixer nodes into their running streams.
+/// slots: 0..4 - audio in, 4..8 - gain of each input
+pub fn update_mixers(
+    query: Query<
+        (&GenericNode, &AudioControl<Mixer>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Mixer>>)>,
+    >,
+) {
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        (0..MIXER_INPUTS).for_each(|input| {
+            let gain = data.get_f32(MIXER_INPUTS + input).unwrap_or(DEFAULT_GAIN);
+            control.set_gain(input, gain);
+        });
+    }
+}
+
+/// Pushes gain and pan of pan nodes into their running streams.
+/// `auto_pan` nodes take the pan from their column on the grid instead of a slot.
+/// slots: 0 - audio in, 1 - gain, 2 - pan
+pub fn update_pans(
+    g_query: Query<&Grid>,
+    query: Query<
+        (&GenericNode, &AudioControl<Pan>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Pan>>)>,
+    >,
+) {
+    let width = g_query
+        .get_single()
+        .map(|grid| grid.dims.0.max(2))
+        .unwrap_or(2);
+
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+        let node = gnode.get_node();
+
+        control.set_gain(data.get_f32(1).unwrap_or(pan::DEFAULT_GAIN));
+
+        match node.name {
+            NodeVarient::AutoPan => {
+                // positions are centered on the grid, the left column is 0.
+                let column = (node.pos.x + width / 2) as f32;
+                control.set_pan((column / (width - 1) as f32 * 2.0 - 1.0).clamp(-1.0, 1.0));
+            }
+            _ => control.set_pan(data.get_f32(2).unwrap_or(pan::DEFAULT_PAN)),
+        }
+    }
+}
+
+/// Plays samplers on a bang and pushes their playback slots into the stream.
+/// slots: 0 - bang, 1 - start, 2 - end, 3 - pitch, 4 - loop mode
+pub fn update_samplers(
+    mut query: Query<
+        (&mut GenericNode, &AudioControl<Sampler>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Sampler>>)>,
+    >,
+) {
+    for (mut gnode, control) in query.iter_mut() {
+        let data = gnode.get_data();
+
+        control.set_range(
+            data.get_f32(1).unwrap_or(sampler::DEFAULT_START),
+            data.get_f32(2).unwrap_or(sampler::DEFAULT_END),
+        );
+        control.set_pitch(data.get_f32(3).unwrap_or(sampler::DEFAULT_PITCH));
+        control.set_loop(LoopMode::from_f32(data.get_f32(4).unwrap_or_default()));
+
+        // the bang is consumed so the next pulse can trigger again.
+        if matches!(data.slot_data.first(), Some(SlotData::Bang(true))) {
+            gnode.get_data_mut().slot_data[0] = SlotData::Bang(false);
+            control.trigger();
+        }
+    }
+}
+
+/// Pushes the slot values of wavetable nodes into their running streams.
+/// slots: 0 - frequency, 1 - amplitude, 2 - position
+pub fn update_wavetables(
+    query: Query<
+        (&GenericNode, &AudioControl<Wavetable>),
+        Or<(Changed<GenericNode>, Added<AudioControl<Wavetable>>)>,
+    >,
+) {
+    for (gnode, control) in query.iter() {
+        let data = gnode.get_data();
+
+        control.set_frequency(data.get_f32(0).unwrap_or(wavetable::DEFAULT_FREQUENCY));
+        control.set_amplitude(data.get_f32(1).unwrap_or(wavetable::DEFAULT_AMPLITUDE));
+        control.set_position(data.get_f32(2).unwrap_or(wavetable::DEFAULT_POSITION));
+    }
+}
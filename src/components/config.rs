@@ -7,6 +7,14 @@ use bevy::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::{
+    components::{
+        osc::{DEFAULT_OSC_PORT, DEFAULT_OSC_TARGET},
+        transport::{DEFAULT_BEATS_PER_BAR, DEFAULT_BPM, DEFAULT_TICKS_PER_BEAT},
+    },
+    dsp::{backend::AudioBackendKind, oscillators::DEFAULT_WAVE_FADE},
+};
+
 #[derive(Asset, TypePath, Debug, Deserialize, Serialize, Resource)]
 pub struct ConfigAsset {
     pub width: u32,
@@ -29,10 +37,62 @@ pub struct ConfigAsset {
     pub grid_offset_y: f32,
 
     pub grid_widget_scale: f32,
+
+    #[serde(default)]
+    pub audio_backend: AudioBackendKind,
+    #[serde(default = "default_audio_file")]
+    pub audio_file: String,
+    #[serde(default)]
+    pub realtime_debug: bool,
+    #[serde(default = "default_wave_fade")]
+    pub wave_fade: f32,
+
+    #[serde(default = "default_bpm")]
+    pub bpm: f32,
+    #[serde(default = "default_ticks_per_beat")]
+    pub ticks_per_beat: u32,
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+    #[serde(default)]
+    pub swing: f32,
+
+    #[serde(default = "default_osc_port")]
+    pub osc_port: u16,
+    #[serde(default = "default_osc_target")]
+    pub osc_target: String,
+}
+
+fn default_audio_file() -> String {
+    "pulsar.wav".to_string()
+}
+
+fn default_wave_fade() -> f32 {
+    DEFAULT_WAVE_FADE
+}
+
+fn default_bpm() -> f32 {
+    DEFAULT_BPM
+}
+
+fn default_ticks_per_beat() -> u32 {
+    DEFAULT_TICKS_PER_BEAT
+}
+
+fn default_beats_per_bar() -> u32 {
+    DEFAULT_BEATS_PER_BAR
+}
+
+fn default_osc_port() -> u16 {
+    DEFAULT_OSC_PORT
+}
+
+fn default_osc_target() -> String {
+    DEFAULT_OSC_TARGET.to_string()
 }
 
 #[derive(Default, Component)]
 pub struct ConfigComp {
+    #[allow(dead_code)]
     pub handle: Handle<ConfigAsset>,
 }
 
@@ -48,6 +108,13 @@ pub fn map_config_resource(config: &mut ConfigAsset, new_config: &ConfigAsset) {
     config.line_offset_y_1 = new_config.line_offset_y_1;
     config.xy_mult = new_config.xy_mult;
     config.xy_rad = new_config.xy_rad;
+    config.wave_fade = new_config.wave_fade;
+    config.bpm = new_config.bpm;
+    config.ticks_per_beat = new_config.ticks_per_beat;
+    config.beats_per_bar = new_config.beats_per_bar;
+    config.swing = new_config.swing;
+    config.osc_port = new_config.osc_port;
+    config.osc_target = new_config.osc_target.clone();
 }
 
 #[derive(Default)]
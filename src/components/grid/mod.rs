@@ -4,7 +4,6 @@ use bevy::{
     ecs::{bundle::Bundle, component::Component, entity::Entity},
     log::info,
     math::Vec3,
-    reflect::Map,
     utils::HashMap,
 };
 
@@ -50,10 +49,8 @@ impl Grid {
 
         data.append(
             &mut (0..dim_x)
-                .into_iter()
-                .map(|x| {
+                .flat_map(|x| {
                     (0..dim_y)
-                        .into_iter()
                         .map(|y| InstanceData {
                             position: Vec3::new(
                                 (((x as f32) - (dim_x as f32 / 2.0)) * offset.0) - (offset.0 / 2.0), // move to -1 1
@@ -66,7 +63,6 @@ impl Grid {
                         })
                         .collect::<Vec<InstanceData>>()
                 })
-                .flatten()
                 .collect::<Vec<InstanceData>>(),
         );
     }
@@ -109,8 +105,8 @@ impl Grid {
     >(
         &mut self,
         node: &T,
-        input_slots: &Vec<S>,
-        output_slots: &Vec<V>,
+        input_slots: &[S],
+        output_slots: &[V],
     ) -> Result<()> {
         if self.exists(node.pos().to_tuple()) {
             return Err(anyhow::anyhow!("collision"));
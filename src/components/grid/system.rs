@@ -45,12 +45,12 @@ pub fn setup_grid(
                 ..Default::default()
             },
             grid,
-            ..Default::default()
         },
         RenderLayers::layer(UI_TARGET),
     ));
 }
 
+#[allow(dead_code)]
 #[derive(Component, Deref, DerefMut)]
 pub struct GridAnimationTimer(Timer);
 
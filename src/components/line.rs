@@ -20,7 +20,15 @@ impl Default for XYLine {
     }
 }
 
-#[derive(Component, Debug, Clone, Default)]
+#[derive(Component, Debug, Clone)]
 pub struct SplitLine {
     pub buffer: [Vec<f32>; 2],
 }
+
+impl Default for SplitLine {
+    fn default() -> Self {
+        Self {
+            buffer: [Vec::with_capacity(SPLIT_LEN), Vec::with_capacity(SPLIT_LEN)],
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Implements loader for standard midi files, notes are read up front and timed in beats.
+
+pub mod record;
+pub mod system;
+
+use bevy::{
+    asset::{io::Reader, Asset, AssetLoader, AsyncReadExt, Handle, LoadContext},
+    ecs::component::Component,
+    reflect::TypePath,
+    utils::{BoxedFuture, HashMap},
+};
+use midly::{MidiMessage, Smf, Timing, TrackEventKind};
+use thiserror::Error;
+
+/// played by midi player blueprints that do not set `node.asset`.
+pub const DEFAULT_MIDI: &str = "midi/arp.mid";
+
+/// A note of a midi file, times are in beats from the start of the file.
+#[derive(Clone, Copy, Debug)]
+pub struct MidiNote {
+    pub beat: f64,
+    pub length: f64,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+impl MidiNote {
+    /// velocity from 0.0 to 1.0.
+    pub fn level(&self) -> f32 {
+        self.velocity as f32 / 127.0
+    }
+}
+
+/// A note starting or ending on a tick, carried by the pulses of midi players.
+/// A level of 0.0 is the note off of the key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub key: u8,
+    pub level: f32,
+}
+
+impl Note {
+    pub fn frequency(&self) -> f32 {
+        key_frequency(self.key)
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.level <= 0.0
+    }
+}
+
+/// equal tempered, a4 is 440 Hz.
+pub fn key_frequency(key: u8) -> f32 {
+    440.0 * 2.0_f32.powf((key as f32 - 69.0) / 12.0)
+}
+
+/// nearest equal tempered key of a frequency.
+pub fn key_from_frequency(frequency_hz: f32) -> u8 {
+    (69.0 + 12.0 * (frequency_hz.max(1.0) / 440.0).log2())
+        .round()
+        .clamp(0.0, 127.0) as u8
+}
+
+/// Notes of every track, sorted by start.
+#[derive(Asset, TypePath, Debug)]
+pub struct MidiAsset {
+    pub notes: Vec<MidiNote>,
+    // end of the last event, in beats.
+    pub beats: f64,
+}
+
+/// Component of a node playing a midi file along with the transport.
+#[derive(Component, Clone)]
+pub struct MidiPlayer {
+    pub handle: Handle<MidiAsset>,
+}
+
+#[derive(Default)]
+pub struct MidiLoader;
+
+impl AssetLoader for MidiLoader {
+    type Asset = MidiAsset;
+    type Settings = ();
+    type Error = MidiLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+
+            parse(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mid", "midi"]
+    }
+}
+
+fn parse(bytes: &[u8]) -> Result<MidiAsset, MidiLoaderError> {
+    let smf = Smf::parse(bytes)?;
+
+    let ticks_per_beat = match smf.header.timing {
+        Timing::Metrical(ticks) => ticks.as_int().max(1) as f64,
+        Timing::Timecode(..) => return Err(MidiLoaderError::Timecode),
+    };
+
+    let mut notes = vec![];
+    let mut end = 0;
+
+    for track in smf.tracks.iter() {
+        let mut tick: u64 = 0;
+        // notes waiting for their note off - (channel, key) -> (start, velocity)
+        let mut open = HashMap::default();
+
+        for event in track.iter() {
+            tick += event.delta.as_int() as u64;
+
+            if let TrackEventKind::Midi { channel, message } = event.kind {
+                let channel = channel.as_int();
+
+                match message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                        open.insert((channel, key.as_int()), (tick, vel.as_int()));
+                    }
+                    // a note on without velocity is a note off.
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        if let Some((start, velocity)) = open.remove(&(channel, key.as_int())) {
+                            notes.push(MidiNote {
+                                beat: start as f64 / ticks_per_beat,
+                                length: (tick - start) as f64 / ticks_per_beat,
+                                key: key.as_int(),
+                                velocity,
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        end = end.max(tick);
+    }
+
+    notes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+    Ok(MidiAsset {
+        notes,
+        beats: end as f64 / ticks_per_beat,
+    })
+}
+
+/// Possible errors that can be produced by [`MidiLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum MidiLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse midi file: {0}")]
+    Parse(#[from] midly::Error),
+    #[error("Timecode based midi files are not supported")]
+    Timecode,
+}
+
+#[cfg(test)]
+mod tests {
+    use midly::{
+        num::{u28, u4, u7},
+        Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+    };
+
+    use super::{key_frequency, parse, MidiLoaderError};
+
+    const TICKS_PER_BEAT: u16 = 96;
+
+    fn event(delta: u32, message: MidiMessage) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Midi {
+                channel: u4::new(0),
+                message,
+            },
+        }
+    }
+
+    fn on(delta: u32, key: u8, vel: u8) -> TrackEvent<'static> {
+        event(
+            delta,
+            MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(vel),
+            },
+        )
+    }
+
+    fn off(delta: u32, key: u8) -> TrackEvent<'static> {
+        event(
+            delta,
+            MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0),
+            },
+        )
+    }
+
+    fn bytes(timing: Timing, track: Vec<TrackEvent<'static>>) -> Vec<u8> {
+        let mut track = track;
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let smf = Smf {
+            header: Header::new(Format::SingleTrack, timing),
+            tracks: vec![track],
+        };
+
+        let mut bytes = vec![];
+        smf.write_std(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn notes_are_timed_in_beats() {
+        let ticks = TICKS_PER_BEAT as u32;
+        let bytes = bytes(
+            Timing::Metrical(TICKS_PER_BEAT.into()),
+            vec![
+                on(0, 60, 100),
+                on(ticks / 2, 64, 127),
+                off(ticks / 2, 60),
+                // a note on without velocity ends the note.
+                on(ticks, 64, 0),
+            ],
+        );
+
+        let midi = parse(&bytes).unwrap();
+        let notes: Vec<_> = midi
+            .notes
+            .iter()
+            .map(|n| (n.beat, n.length, n.key, n.velocity))
+            .collect();
+
+        assert_eq!(notes, vec![(0.0, 1.0, 60, 100), (0.5, 1.5, 64, 127)]);
+        assert_eq!(midi.beats, 2.0);
+    }
+
+    #[test]
+    fn note_pitch_and_level() {
+        let bytes = bytes(
+            Timing::Metrical(TICKS_PER_BEAT.into()),
+            vec![on(0, 69, 127), off(10, 69)],
+        );
+        let note = parse(&bytes).unwrap().notes[0];
+
+        assert!((key_frequency(note.key) - 440.0).abs() < 1e-3);
+        assert!((note.level() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn timecode_files_are_rejected() {
+        let bytes = bytes(Timing::Timecode(midly::Fps::Fps25, 40), vec![]);
+
+        assert!(matches!(parse(&bytes), Err(MidiLoaderError::Timecode)));
+    }
+}
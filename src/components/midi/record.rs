@@ -0,0 +1,246 @@
+use std::{io, path::Path};
+
+use bevy::ecs::{entity::Entity, system::Resource};
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::components::nodes::{
+    generic::GenericNode,
+    types::{NodeVarient, ParentNode},
+};
+
+use super::key_from_frequency;
+
+/// resolution of written files, plenty for the ticks of the transport.
+const TICKS_PER_BEAT: u16 = 480;
+/// key of bangs into instruments without a frequency, middle c.
+const DEFAULT_KEY: u8 = 60;
+const VELOCITY: u8 = 100;
+
+/// A bang recorded from the grid, times are in beats from the start of the take.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordedNote {
+    pub beat: f64,
+    pub length: f64,
+    pub key: u8,
+}
+
+struct RecordedTrack {
+    entity: Entity,
+    name: String,
+    notes: Vec<RecordedNote>,
+}
+
+/// Records every bang delivered to an instrument, one track per instrument node.
+/// Stopping the transport rewinds it, the next bang then starts a new take.
+#[derive(Resource, Default)]
+pub struct MidiRecorder {
+    tracks: Vec<RecordedTrack>,
+    last_beat: f64,
+}
+
+impl MidiRecorder {
+    pub fn record(&mut self, entity: Entity, name: String, note: RecordedNote) {
+        if note.beat < self.last_beat {
+            self.clear();
+        }
+        self.last_beat = note.beat;
+
+        match self.tracks.iter_mut().find(|track| track.entity == entity) {
+            Some(track) => track.notes.push(note),
+            None => self.tracks.push(RecordedTrack {
+                entity,
+                name,
+                notes: vec![note],
+            }),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.last_beat = 0.0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// writes the take as a multi track midi file, tempo and meter are taken from the transport.
+    pub fn save(&self, path: impl AsRef<Path>, bpm: f32, beats_per_bar: u32) -> io::Result<()> {
+        let tempo = TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(
+                (60_000_000.0 / bpm as f64) as u32,
+            ))),
+        };
+        // the meter is always written in quarter notes.
+        let meter = TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::TimeSignature(
+                beats_per_bar.clamp(1, 255) as u8,
+                2,
+                24,
+                8,
+            )),
+        };
+
+        let mut tracks = vec![vec![tempo, meter, end_of_track()]];
+
+        tracks.extend(self.tracks.iter().map(|track| {
+            let mut events = vec![TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::TrackName(track.name.as_bytes())),
+            }];
+            events.extend(note_events(&track.notes));
+            events.push(end_of_track());
+
+            events
+        }));
+
+        let smf = Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(TICKS_PER_BEAT))),
+            tracks,
+        };
+
+        smf.save(path)
+    }
+}
+
+/// key of the note an instrument plays when banged, `None` for nodes that are not instruments.
+pub fn instrument_key(gnode: &GenericNode) -> Option<u8> {
+    let data = gnode.get_data();
+
+    match gnode.get_node().name {
+        NodeVarient::LuaPulse => Some(data.get_f32(1).map_or(DEFAULT_KEY, key_from_frequency)),
+        NodeVarient::Generator(_) | NodeVarient::Wavetable => {
+            Some(data.get_f32(0).map_or(DEFAULT_KEY, key_from_frequency))
+        }
+        // samples play at their recorded pitch, the pitch slot moves them in semitones.
+        NodeVarient::Sampler => Some(
+            (DEFAULT_KEY as f32 + data.get_f32(3).unwrap_or_default())
+                .round()
+                .clamp(0.0, 127.0) as u8,
+        ),
+        _ => None,
+    }
+}
+
+fn note_events<'a>(notes: &[RecordedNote]) -> Vec<TrackEvent<'a>> {
+    // (tick, message) - note offs sort before note ons on the same tick.
+    let mut messages = notes
+        .iter()
+        .flat_map(|note| {
+            let on = to_ticks(note.beat);
+            let off = to_ticks(note.beat + note.length).max(on + 1);
+            let key = u7::new(note.key);
+
+            [
+                (
+                    off,
+                    MidiMessage::NoteOff {
+                        key,
+                        vel: u7::new(0),
+                    },
+                ),
+                (
+                    on,
+                    MidiMessage::NoteOn {
+                        key,
+                        vel: u7::new(VELOCITY),
+                    },
+                ),
+            ]
+        })
+        .collect::<Vec<(u32, MidiMessage)>>();
+
+    messages.sort_by_key(|(tick, message)| (*tick, matches!(message, MidiMessage::NoteOn { .. })));
+
+    let mut last = 0;
+    messages
+        .into_iter()
+        .map(|(tick, message)| {
+            let delta = tick - last;
+            last = tick;
+
+            TrackEvent {
+                delta: u28::new(delta),
+                kind: TrackEventKind::Midi {
+                    channel: u4::new(0),
+                    message,
+                },
+            }
+        })
+        .collect()
+}
+
+fn to_ticks(beat: f64) -> u32 {
+    (beat * TICKS_PER_BEAT as f64).round() as u32
+}
+
+fn end_of_track<'a>() -> TrackEvent<'a> {
+    TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::Entity;
+
+    use super::{super::parse, MidiRecorder, RecordedNote, VELOCITY};
+
+    fn note(beat: f64, length: f64, key: u8) -> RecordedNote {
+        RecordedNote { beat, length, key }
+    }
+
+    #[test]
+    fn saved_take_parses_back() {
+        let (a, b) = (Entity::from_raw(1), Entity::from_raw(2));
+
+        let mut recorder = MidiRecorder::default();
+        recorder.record(a, "a".to_string(), note(0.0, 0.5, 60));
+        recorder.record(b, "b".to_string(), note(0.5, 0.25, 67));
+        recorder.record(a, "a".to_string(), note(1.0, 1.0, 64));
+        // a note shorter than a tick still gets a note off.
+        recorder.record(b, "b".to_string(), note(2.0, 0.0, 72));
+
+        let path = std::env::temp_dir().join(format!("pulsar-take-{}.mid", std::process::id()));
+        recorder.save(&path, 120.0, 4).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let midi = parse(&bytes).unwrap();
+        let notes: Vec<_> = midi
+            .notes
+            .iter()
+            .map(|n| (n.beat, n.key, n.velocity))
+            .collect();
+
+        assert_eq!(
+            notes,
+            vec![
+                (0.0, 60, VELOCITY),
+                (0.5, 67, VELOCITY),
+                (1.0, 64, VELOCITY),
+                (2.0, 72, VELOCITY),
+            ]
+        );
+        assert_eq!(midi.beats, 2.0 + 1.0 / super::TICKS_PER_BEAT as f64);
+    }
+
+    #[test]
+    fn rewinding_starts_a_new_take() {
+        let entity = Entity::from_raw(1);
+
+        let mut recorder = MidiRecorder::default();
+        recorder.record(entity, "a".to_string(), note(4.0, 1.0, 60));
+        recorder.record(entity, "a".to_string(), note(0.0, 1.0, 62));
+
+        assert_eq!(recorder.tracks.len(), 1);
+        assert_eq!(recorder.tracks[0].notes.len(), 1);
+        assert_eq!(recorder.tracks[0].notes[0].key, 62);
+    }
+}
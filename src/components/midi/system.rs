@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    asset::Assets,
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        system::{Query, Res},
+    },
+    input::{keyboard::KeyCode, Input},
+    log::{error, info},
+};
+
+use crate::components::{
+    nodes::{generic::types::DataPulseEvent, types::SlotData},
+    transport::Transport,
+};
+
+use super::{record::MidiRecorder, MidiAsset, MidiNote, MidiPlayer, Note};
+
+/// folder recorded takes are written to, next to the executable.
+const RECORDINGS: &str = "recordings";
+
+// output slots of the midi player blueprint.
+const BANG_SLOT: usize = 0;
+const NOTE_SLOT: usize = 1;
+const VELOCITY_SLOT: usize = 2;
+
+/// Sends the notes starting or ending on the current tick out of midi player nodes.
+/// Files loop, their length is rounded up to whole bars so loops stay on the grid.
+/// Every note of the tick rides on one note pulse, the bang and velocity pulses follow the
+/// highest note starting on it.
+pub fn play_midi(
+    transport: Res<Transport>,
+    midi_assets: Res<Assets<MidiAsset>>,
+    query: Query<(Entity, &MidiPlayer)>,
+    mut ev_data_pulse: EventWriter<DataPulseEvent>,
+) {
+    let ticks_per_beat = transport.ticks_per_beat() as f64;
+    let beats_per_bar = transport.beats_per_bar() as f64;
+
+    for (entity, player) in query.iter() {
+        let Some(midi) = midi_assets.get(&player.handle) else {
+            continue;
+        };
+
+        let length = (midi.beats / beats_per_bar).ceil().max(1.0) * beats_per_bar;
+
+        let from = (transport.tick() as f64 / ticks_per_beat) % length;
+        let to = from + 1.0 / ticks_per_beat;
+        let notes = tick_notes(&midi.notes, length, from..to);
+
+        let highest = notes
+            .iter()
+            .filter(|note| !note.is_off())
+            .max_by_key(|note| note.key);
+
+        if let Some(note) = highest {
+            [
+                (BANG_SLOT, SlotData::Bang(true)),
+                (VELOCITY_SLOT, SlotData::F32(note.level)),
+            ]
+            .into_iter()
+            .for_each(|(slot_idx, data)| {
+                ev_data_pulse.send(DataPulseEvent {
+                    entity,
+                    slot_idx,
+                    data,
+                })
+            });
+        }
+
+        if !notes.is_empty() {
+            ev_data_pulse.send(DataPulseEvent {
+                entity,
+                slot_idx: NOTE_SLOT,
+                data: SlotData::Notes(notes),
+            });
+        }
+    }
+}
+
+/// Note offs and note ons of the notes ending or starting within `tick` of a loop `length` beats
+/// long. Offs come first, a key ending and starting again on the tick is played again.
+fn tick_notes(notes: &[MidiNote], length: f64, tick: Range<f64>) -> Vec<Note> {
+    let on_tick = |beat: f64| tick.contains(&(beat % length));
+
+    let offs = notes
+        .iter()
+        .filter(|note| on_tick(note.beat + note.length))
+        .map(|note| Note {
+            key: note.key,
+            level: 0.0,
+        });
+
+    let ons = notes.iter().filter(|note| on_tick(note.beat)).map(|note| Note {
+        key: note.key,
+        level: note.level(),
+    });
+
+    offs.chain(ons).collect()
+}
+
+// temporary key binding - F2 writes the recorded take to a midi file.
+pub fn export_midi_keys(
+    keys: Res<Input<KeyCode>>,
+    recorder: Res<MidiRecorder>,
+    transport: Res<Transport>,
+) {
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    if recorder.is_empty() {
+        info!("nothing recorded yet");
+        return;
+    }
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = format!("{}/pulsar-{}.mid", RECORDINGS, secs);
+
+    let res = fs::create_dir_all(RECORDINGS)
+        .and_then(|_| recorder.save(&path, transport.bpm(), transport.beats_per_bar()));
+
+    match res {
+        Ok(_) => info!("recording written to {}", path),
+        Err(err) => error!("cannot write recording to {} - {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(beat: f64, length: f64, key: u8) -> MidiNote {
+        MidiNote {
+            beat,
+            length,
+            key,
+            velocity: 127,
+        }
+    }
+
+    #[test]
+    fn every_note_of_a_tick_is_sent() {
+        let notes = [note(0.0, 1.0, 60), note(0.0, 0.5, 64), note(0.5, 0.5, 67)];
+
+        let keys = |tick| {
+            tick_notes(&notes, 4.0, tick)
+                .iter()
+                .map(|note| (note.key, note.is_off()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(0.0..0.25), vec![(60, false), (64, false)]);
+        assert_eq!(keys(0.25..0.5), vec![]);
+        assert_eq!(keys(0.5..0.75), vec![(64, true), (67, false)]);
+        assert_eq!(keys(1.0..1.25), vec![(60, true), (67, true)]);
+    }
+
+    #[test]
+    fn notes_ending_on_the_loop_are_released_on_the_first_tick() {
+        let notes = [note(0.0, 1.0, 60), note(3.0, 1.0, 62)];
+        let sent = tick_notes(&notes, 4.0, 0.0..0.25);
+
+        assert_eq!(sent[0], Note { key: 62, level: 0.0 });
+        assert_eq!(sent[1].key, 60);
+        assert!(!sent[1].is_off());
+    }
+}
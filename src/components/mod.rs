@@ -2,6 +2,10 @@ pub mod config;
 pub mod grid;
 pub mod line;
 pub mod lua;
+pub mod midi;
 pub mod nodes;
+pub mod osc;
 pub mod player;
+pub mod sample;
+pub mod transport;
 pub mod audio;
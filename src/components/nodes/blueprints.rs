@@ -21,6 +21,7 @@ use crate::{
             types::{ParentNode, SlotData, SlotType},
         },
     },
+    dsp::{filters::FilterKind, generators::GeneratorKind},
     lua::{init_instance, load_fn},
 };
 
@@ -40,6 +41,36 @@ pub fn init_temp_blueprints(mut commands: Commands, asset_server: Res<AssetServe
         Position::new(0, 0),
     );
 
+    GeneratorKind::all().iter().for_each(|kind| {
+        load_node(
+            &mut commands,
+            &asset_server,
+            ChannelType::Generator,
+            kind.name().to_string(),
+            Position::new(0, 0),
+        );
+    });
+
+    // samples and wavetables are native dsp as well, the node only points at the file.
+    ["sampler", "wavetable"].iter().for_each(|name| {
+        load_node(
+            &mut commands,
+            &asset_server,
+            ChannelType::Generator,
+            name.to_string(),
+            Position::new(0, 0),
+        );
+    });
+
+    // the midi player has no dsp, it only sends pulses.
+    load_node(
+        &mut commands,
+        &asset_server,
+        ChannelType::Generator,
+        "midi_player".to_string(),
+        Position::new(0, 0),
+    );
+
     load_node(
         &mut commands,
         &asset_server,
@@ -48,6 +79,28 @@ pub fn init_temp_blueprints(mut commands: Commands, asset_server: Res<AssetServe
         Position::new(1, 0),
     );
 
+    ["envelope", "delay", "reverb", "mixer", "pan", "auto_pan"]
+        .iter()
+        .for_each(|name| {
+            load_node(
+                &mut commands,
+                &asset_server,
+                ChannelType::Transmitter,
+                name.to_string(),
+                Position::new(1, 0),
+            );
+        });
+
+    FilterKind::all().iter().for_each(|kind| {
+        load_node(
+            &mut commands,
+            &asset_server,
+            ChannelType::Transmitter,
+            kind.name().to_string(),
+            Position::new(1, 0),
+        );
+    });
+
     load_node(
         &mut commands,
         &asset_server,
@@ -93,60 +146,59 @@ pub fn initialize_node<T: NodeTrait + ParentNode>(
     lua_assets: &Res<Assets<LuaAsset>>,
     node: &mut T,
 ) -> bool {
-    match node.get_lua() {
-        Some(lua) => {
-            let lua = lua.lock().unwrap();
+    if let Some(lua) = node.get_lua() {
+        let lua = lua.lock().unwrap();
 
-            let mut new_node = None;
-            let mut new_data = None;
+        let mut new_node = None;
+        let mut new_data = None;
 
-            let mut res = true;
+        let mut res = true;
 
-            node.get_lua_handles().unwrap().iter().for_each(|handle| {
-                if let LuaType::Node = handle.ltype {
-                    let lua_asset = lua_assets.get(handle.handle.clone()); // TODO: Handle unwrap
+        node.get_lua_handles().unwrap().iter().for_each(|handle| {
+            if let LuaType::Node = handle.ltype {
+                let lua_asset = lua_assets.get(handle.handle.clone()); // TODO: Handle unwrap
 
-                    match lua_asset {
-                        Some(lua_asset) => {
-                            load_fn(&lua, &node.get_node().name.to_string(), &lua_asset.script);
-                        }
-                        None => res = false,
+                match lua_asset {
+                    Some(lua_asset) => {
+                        load_fn(&lua, &node.get_node().name.to_string(), &lua_asset.script);
                     }
+                    None => res = false,
                 }
-            });
-
-            // all lua functions loaded, now we can run the reset function
-            lua.context(|ctx| {
-                let n = ctx.load("node").eval::<Node>();
-                let nd = ctx.load("data").eval::<NodeData>();
-
-                if n.is_err() || nd.is_err() {
-                    res = false;
-                }
+            }
+        });
 
-                new_node = Some(n.unwrap());
-                new_data = Some(nd.unwrap());
-            });
+        // all lua functions loaded, now we can run the reset function
+        lua.context(|ctx| {
+            let n = ctx.load("node").eval::<Node>();
+            let nd = ctx.load("data").eval::<NodeData>();
 
-            match res {
-                true => {}
-                false => return false,
+            if n.is_err() || nd.is_err() {
+                res = false;
             }
 
-            drop(lua);
+            new_node = Some(n.unwrap());
+            new_data = Some(nd.unwrap());
+        });
 
-            *node.get_node_mut() = new_node.unwrap();
-            *node.get_data_mut() = new_data.unwrap();
+        match res {
+            true => {}
+            false => return false,
         }
-        _ => (),
+
+        drop(lua);
+
+        *node.get_node_mut() = new_node.unwrap();
+        *node.get_data_mut() = new_data.unwrap();
     };
 
     let n = node.get_node().clone();
     let data = node.get_data_mut();
 
+    // slots without a default value from lua get zeroed.
     n.slots
         .clone()
         .into_iter()
+        .skip(data.slot_data.len())
         .for_each(|slot| match slot.slot_type {
             SlotType::F32 => data.slot_data.push(SlotData::F32(f32::default())),
             SlotType::I32 => data.slot_data.push(SlotData::I32(i32::default())),
@@ -160,7 +212,7 @@ pub fn initialize_node<T: NodeTrait + ParentNode>(
     info!("Node initialized - {:?}", node.get_node());
     info!("Node Data initialized - {:?}", node.get_data());
 
-    return true;
+    true
 }
 
 pub fn load_native_node_instrument(
@@ -223,6 +275,54 @@ pub fn load_native_node_instrument(
     ));
 }
 
+pub fn load_native_node_generator(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    name: String,
+    pos: Position,
+) {
+    // base logic
+    let base_node_handle: Handle<LuaAsset> = asset_server.load("lua/common/node.lua");
+    let base_instrument_node_handle: Handle<LuaAsset> =
+        asset_server.load("lua/common/instrument/node.lua");
+
+    // custom logic, generators are native dsp so there is no wave.
+    let lua_node_handle: Handle<LuaAsset> =
+        asset_server.load(format!("lua/nodes/instrument/{}/node.lua", name));
+
+    let lua = init_instance();
+
+    commands.spawn((
+        GenericNode::Native(NativeNode {
+            node: Node {
+                pos,
+                ..Default::default()
+            },
+            data: NodeData {
+                ..Default::default()
+            },
+            lua: Some(Mutex::new(lua)),
+            handles: Some(vec![
+                LuaHandle {
+                    ltype: LuaType::Node,
+                    handle: base_node_handle,
+                },
+                LuaHandle {
+                    ltype: LuaType::Node,
+                    handle: base_instrument_node_handle,
+                },
+                LuaHandle {
+                    ltype: LuaType::Node,
+                    handle: lua_node_handle,
+                },
+            ]),
+        }),
+        IsLuaNode,
+        NotSetup,
+        NodeBP,
+    ));
+}
+
 pub fn load_native_node_transmitter(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
@@ -330,6 +430,9 @@ pub fn load_node(
         ChannelType::Instrument => {
             load_native_node_instrument(commands, asset_server, name, pos);
         }
+        ChannelType::Generator => {
+            load_native_node_generator(commands, asset_server, name, pos);
+        }
         ChannelType::Transmitter => {
             load_native_node_transmitter(commands, asset_server, name, pos);
         }
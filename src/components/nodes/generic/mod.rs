@@ -15,11 +15,13 @@ use super::{
 
 #[derive(Component, Debug)]
 pub enum GenericNode {
+    #[allow(dead_code)]
     Lua(LuaNode),
     Native(NativeNode),
 }
 
 impl GenericNode {
+    #[allow(dead_code)]
     pub fn get_lua_node(&self) -> Option<&LuaNode> {
         match self {
             GenericNode::Lua(node) => Some(node),
@@ -27,6 +29,7 @@ impl GenericNode {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_native_node(&self) -> Option<&NativeNode> {
         match self {
             GenericNode::Native(node) => Some(node),
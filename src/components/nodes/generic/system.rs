@@ -1,9 +1,7 @@
-use std::{ops::Mul, process::Output};
+use std::{mem::discriminant, ops::Mul};
 
-use anyhow::Chain;
 use bevy::{
     asset::AssetServer,
-    audio,
     ecs::{
         entity::Entity,
         event::{EventReader, EventWriter},
@@ -11,28 +9,38 @@ use bevy::{
         system::{Commands, Query, Res, ResMut},
     },
     hierarchy::{DespawnRecursiveExt, Parent},
-    log::info,
+    log::{info, warn},
     math::Vec2,
     transform::components::Transform,
 };
-use knyst::knyst_commands;
 
 use crate::{
     components::{
         audio::AudioGraph,
         config::ConfigAsset,
         grid::Grid,
+        midi::{
+            key_from_frequency,
+            record::{instrument_key, MidiRecorder, RecordedNote},
+        },
         nodes::{
             generic::util::{calculate_grid_pos, construct_pulse_node},
-            lua::get_lua_wave_handles,
             types::{AudioNode, InputSlot, NodeType, NodeVarient, ParentNode, Pulse, SlotData},
             util::spawn_node_with_text,
         },
+        osc::client::{OscClient, PulseOsc},
+        transport::Transport,
+    },
+    dsp::{
+        delay::Delay, envelope::Envelope, filters::Filter, mixer::BusSend, pan::Pan, read::Read,
+        reverb::Reverb, ChainType, Dsp, TChain,
     },
-    dsp::{oscillators::Oscillator, read::Read, ChainType, Dsp, TChain},
 };
 
-use super::{types::AudioNodePulseEvent, GenericNode};
+use super::{
+    types::{AudioNodePulseEvent, DataPulseEvent, NoteEvent},
+    GenericNode,
+};
 
 pub fn spawn_audio_pulses(
     mut ev_audio_pulse_event: EventReader<AudioNodePulseEvent>,
@@ -41,6 +49,7 @@ pub fn spawn_audio_pulses(
     mut g_query: Query<&mut Grid>,
     asset_server: Res<AssetServer>,
     query: Query<(Entity, &GenericNode)>,
+    osc: Res<OscClient>,
 ) {
     if let Ok(mut grid) = g_query.get_single_mut() {
         for ev in ev_audio_pulse_event.read() {
@@ -51,38 +60,98 @@ pub fn spawn_audio_pulses(
 
             let slot = &node.get_node().output_slots[ev.slot_idx];
 
-            match slot.signal_type {
-                NodeType::SignalLink => {
-                    let direction = node.get_node().output_slots[ev.slot_idx].direction.clone();
-                    let name = NodeVarient::AudioProd;
-                    let display = "D".to_string();
-                    let ntype = vec![NodeType::Prod];
-                    let data = SlotData::Bang(true);
-
-                    let pulse = Pulse {
-                        slot_idx: ev.slot_idx,
-                        direction: direction.clone(),
-                        original_entity: entity,
-                    };
-                    let pos = calculate_grid_pos(node, &pulse, direction.clone());
-
-                    info!("pos: {:?}", pos);
-                    let node = construct_pulse_node(pos.clone(), name, display, ntype, data);
-                    let e = spawn_node_with_text(
-                        &mut grid,
-                        &config,
-                        &mut commands,
-                        &asset_server,
-                        node,
-                    );
-                    commands.entity(e).insert(pulse);
-                }
-                _ => (),
+            if let NodeType::SignalLink = slot.signal_type {
+                let direction = node.get_node().output_slots[ev.slot_idx].direction;
+                let name = NodeVarient::AudioProd;
+                let display = "D".to_string();
+                let ntype = vec![NodeType::Prod];
+                // pulses carry the value of the emitter, a bang when it has none.
+                let data = match &node.get_data().data {
+                    SlotData::None => SlotData::Bang(true),
+                    data => data.clone(),
+                };
+
+                let pulse = Pulse {
+                    slot_idx: ev.slot_idx,
+                    direction,
+                    original_entity: entity,
+                };
+                let pos = calculate_grid_pos(node, &pulse, direction);
+
+                info!("pos: {:?}", pos);
+                osc.pulse(
+                    PulseOsc::Spawn,
+                    pos,
+                    node.get_node().name.to_string(),
+                    ev.slot_idx,
+                );
+
+                let node = construct_pulse_node(pos, name, display, ntype, data);
+                let e = spawn_node_with_text(
+                    &mut grid,
+                    &config,
+                    &mut commands,
+                    &asset_server,
+                    node,
+                );
+                commands.entity(e).insert(pulse);
             }
         }
     }
 }
 
+pub fn spawn_data_pulses(
+    mut ev_data_pulse: EventReader<DataPulseEvent>,
+    mut commands: Commands,
+    config: Res<ConfigAsset>,
+    mut g_query: Query<&mut Grid>,
+    asset_server: Res<AssetServer>,
+    query: Query<&GenericNode>,
+    osc: Res<OscClient>,
+) {
+    if let Ok(mut grid) = g_query.get_single_mut() {
+        for ev in ev_data_pulse.read() {
+            let Ok(node) = query.get(ev.entity) else {
+                continue;
+            };
+
+            let Some(slot) = node.get_node().output_slots.get(ev.slot_idx) else {
+                continue;
+            };
+
+            let direction = slot.direction;
+            let pulse = Pulse {
+                slot_idx: ev.slot_idx,
+                direction,
+                original_entity: ev.entity,
+            };
+            let pos = calculate_grid_pos(node, &pulse, direction);
+
+            // a pulse still on its way out of the slot, the value is dropped.
+            if grid.get_entity(pos.to_tuple()).is_some() {
+                continue;
+            }
+
+            osc.pulse(
+                PulseOsc::Spawn,
+                pos,
+                node.get_node().name.to_string(),
+                ev.slot_idx,
+            );
+
+            let node = construct_pulse_node(
+                pos,
+                NodeVarient::DataProd,
+                "d".to_string(),
+                vec![NodeType::Prod],
+                ev.data.clone(),
+            );
+            let e = spawn_node_with_text(&mut grid, &config, &mut commands, &asset_server, node);
+            commands.entity(e).insert(pulse);
+        }
+    }
+}
+
 // main scheduled system for pulses.
 pub fn tick_pulses(
     mut commands: Commands,
@@ -90,10 +159,14 @@ pub fn tick_pulses(
     config: Res<ConfigAsset>,
     mut graph: ResMut<AudioGraph>,
     mut query: Query<(Entity, &mut GenericNode, &Pulse, &mut Transform), With<Pulse>>,
-    mut node_query: Query<(&mut GenericNode), Without<Pulse>>,
-    mut audio_node_query: Query<&AudioNode>,
+    mut node_query: Query<&mut GenericNode , Without<Pulse>>,
+    audio_node_query: Query<&AudioNode>,
     input_node_query: Query<(&Parent, &mut InputSlot)>,
     mut ev_audio_pulse: EventWriter<AudioNodePulseEvent>,
+    mut ev_note: EventWriter<NoteEvent>,
+    transport: Res<Transport>,
+    mut recorder: ResMut<MidiRecorder>,
+    osc: Res<OscClient>,
 ) {
     // box size config.
     let box_size = Vec2::new(config.grid_offset_x, config.grid_offset_y);
@@ -123,10 +196,14 @@ pub fn tick_pulses(
 
                 grid.remove_from_grid(current_pos.to_tuple());
                 commands.entity(entity).despawn_recursive();
-                ev_audio_pulse.send(AudioNodePulseEvent {
-                    entity: pulse.original_entity,
-                    slot_idx: pulse.slot_idx,
-                });
+
+                // data pulses are sent by their node, only audio pulses repeat.
+                if let NodeVarient::AudioProd = node.name {
+                    ev_audio_pulse.send(AudioNodePulseEvent {
+                        entity: pulse.original_entity,
+                        slot_idx: pulse.slot_idx,
+                    });
+                }
                 continue;
             }
 
@@ -140,22 +217,85 @@ pub fn tick_pulses(
                         let idx = input_slot.idx;
 
                         // get root node of input.
-                        if let Ok(gnode) = node_query.get_mut(parent_entity.get()) {
+                        let parent_entity = parent_entity.get();
+                        if let Ok(mut gnode) = node_query.get_mut(parent_entity) {
+                            osc.pulse(
+                                PulseOsc::Hit,
+                                new_pos,
+                                gnode.get_node().name.to_string(),
+                                idx,
+                            );
+
                             // check if the node is a signal const node, and if the signal type is audio.
-                            match (&gnode.get_node().slots[idx].signal_type, &node.name) {
+                            match (gnode.get_node().slots[idx].signal_type.clone(), &node.name) {
                                 (NodeType::SignalConst, NodeVarient::AudioProd) => {
-                                    connect_audio(
+                                    let connected = connect_audio(
                                         &audio_node_query,
                                         pulse,
                                         &mut graph,
                                         gnode,
-                                        entity,
+                                        parent_entity,
+                                        idx,
                                     );
+
+                                    // the emitting node is not part of a chain yet, keep pulsing.
+                                    if !connected {
+                                        ev_audio_pulse.send(AudioNodePulseEvent {
+                                            entity: pulse.original_entity,
+                                            slot_idx: pulse.slot_idx,
+                                        });
+                                    }
+                                }
+                                (
+                                    NodeType::Signal,
+                                    NodeVarient::AudioProd | NodeVarient::DataProd,
+                                ) => {
+                                    // signal slots take the payload of the pulse.
+                                    let keys = deliver_pulse(
+                                        &mut gnode,
+                                        parent_entity,
+                                        idx,
+                                        &node_data.data,
+                                        &mut ev_note,
+                                    );
+
+                                    // notes played by instruments are recorded.
+                                    if !keys.is_empty() {
+                                        let ticks_per_beat = transport.ticks_per_beat() as f64;
+                                        // the transport has already counted this tick.
+                                        let tick = transport.tick().saturating_sub(1);
+
+                                        for key in keys {
+                                            recorder.record(
+                                                parent_entity,
+                                                format!(
+                                                    "{} ({}, {})",
+                                                    gnode.get_node().name,
+                                                    gnode.get_node().pos.x,
+                                                    gnode.get_node().pos.y
+                                                ),
+                                                RecordedNote {
+                                                    beat: tick as f64 / ticks_per_beat,
+                                                    length: 1.0 / ticks_per_beat,
+                                                    key,
+                                                },
+                                            );
+                                        }
+                                    }
+
+                                    // audio emitters keep pulsing so the distance to the slot
+                                    // sets the rhythm, data pulses are sent by their node.
+                                    if let NodeVarient::AudioProd = node.name {
+                                        ev_audio_pulse.send(AudioNodePulseEvent {
+                                            entity: pulse.original_entity,
+                                            slot_idx: pulse.slot_idx,
+                                        });
+                                    }
                                 }
                                 _ => (),
                             }
                         }
-                    } else {
+                    } else if let NodeVarient::AudioProd = node.name {
                         info!("sending audio pulse event.");
                         ev_audio_pulse.send(AudioNodePulseEvent {
                             entity: pulse.original_entity,
@@ -172,14 +312,102 @@ pub fn tick_pulses(
                     grid.move_entity(entity, current_pos.to_tuple(), new_pos.to_tuple());
                     tform.translation = new_pos.to_vec2().extend(0.).mul(box_size.extend(0.0));
                     node.pos = new_pos;
+
+                    if let Ok(emitter) = node_query.get(pulse.original_entity) {
+                        osc.pulse(
+                            PulseOsc::Move,
+                            new_pos,
+                            emitter.get_node().name.to_string(),
+                            pulse.slot_idx,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes the payload of a pulse into a signal slot, returns the keys it plays on the instrument.
+/// Lua instruments get their notes as events so every pulse of a tick plays: a bang plays the
+/// frequency slot, a frequency into the bang slot plays its own pitch and notes into the
+/// frequency slot are held until their note off.
+fn deliver_pulse(
+    gnode: &mut GenericNode,
+    entity: Entity,
+    idx: usize,
+    data: &SlotData,
+    ev_note: &mut EventWriter<NoteEvent>,
+) -> Vec<u8> {
+    let mut keys = vec![];
+
+    if let NodeVarient::LuaPulse = gnode.get_node().name {
+        match (idx, data) {
+            (0, SlotData::Bang(true)) => {
+                ev_note.send(NoteEvent::Trigger {
+                    entity,
+                    frequency: None,
+                });
+                return instrument_key(gnode).into_iter().collect();
+            }
+            (0, SlotData::F32(frequency)) => {
+                ev_note.send(NoteEvent::Trigger {
+                    entity,
+                    frequency: Some(*frequency),
+                });
+                return vec![key_from_frequency(*frequency)];
+            }
+            (1, SlotData::Notes(notes)) => {
+                for note in notes.iter() {
+                    match note.is_off() {
+                        true => ev_note.send(NoteEvent::Off {
+                            entity,
+                            key: note.key,
+                        }),
+                        false => {
+                            ev_note.send(NoteEvent::On {
+                                entity,
+                                note: *note,
+                            });
+                            keys.push(note.key);
+                        }
+                    }
                 }
             }
+            _ => (),
+        }
+    }
+
+    let Some(slot) = gnode.get_data_mut().slot_data.get_mut(idx) else {
+        return keys;
+    };
+
+    match (slot, data) {
+        // frequency slots follow the highest note starting on the tick.
+        (SlotData::F32(frequency), SlotData::Notes(notes)) => {
+            if let Some(note) = notes
+                .iter()
+                .filter(|note| !note.is_off())
+                .max_by_key(|note| note.key)
+            {
+                *frequency = note.frequency();
+            }
         }
+        (slot, data) if discriminant(slot) == discriminant(data) => *slot = data.clone(),
+        _ => return keys,
     }
+
+    // bangs into other instruments play their current pitch.
+    if let SlotData::Bang(true) = data {
+        keys.extend(instrument_key(gnode));
+    }
+
+    keys
 }
 
+
 // this function is used for adding to the audio graph.
 // does not deal with any data, just linking to the ast.
+// returns false when the emitting node is not linked to any chain yet.
 // TODO: need to draw some kind of line showing the connection
 fn connect_audio(
     audio_node_query: &Query<'_, '_, &AudioNode>,
@@ -187,48 +415,126 @@ fn connect_audio(
     graph: &mut ResMut<'_, AudioGraph>,
     gnode: bevy::prelude::Mut<'_, GenericNode>,
     entity: Entity,
-) {
-    // get audio node.
-    let audio_node = audio_node_query.get(pulse.original_entity).unwrap();
+    slot_idx: usize,
+) -> bool {
+    // instruments own their chain, transmitters are found through the link they created.
+    let idx = match audio_node_query
+        .get(pulse.original_entity)
+        .ok()
+        .and_then(|audio_node| audio_node.idx)
+        .or_else(|| graph.chain_idx(pulse.original_entity))
+    {
+        Some(idx) => idx,
+        None => return false,
+    };
+
+    // the link is already part of the chain.
+    if graph.get_chain()[idx].contains(entity) {
+        return true;
+    }
+
+    let name = gnode.get_node().name.clone();
+
+    // a node already reached by another chain keeps its node, this chain is sent into it.
+    let shared = !matches!(name, NodeVarient::Mixer | NodeVarient::AudioOut)
+        && graph.chain_idx(entity).is_some();
+
+    let link = match shared {
+        true => {
+            info!("sending into the node of another chain - {}", name);
+
+            let send = BusSend {
+                bus: entity,
+                input: 0,
+                node: None,
+            };
+            Some(TChain::dsp(Dsp::BusSend(send), Some(entity)))
+        }
+        false => link_chain(name.clone(), entity, slot_idx),
+    };
+
+    let link = match link {
+        Some(link) => link,
+        None => {
+            warn!("skipping link - {} can not be linked into a chain.", name);
+            return true;
+        }
+    };
 
     // check if the chain is already setup.
-    match graph.get_chain_mut()[audio_node.idx.unwrap()].t.as_mut() {
-        ChainType::ChainList(ref mut l) => match gnode.get_node().name {
-            NodeVarient::LuaRead => {
-                info!("inserting pulse");
-
-                let osc = Read;
-                l.push(TChain::vec(
-                    vec![TChain::dsp(Dsp::Read(osc), Some(entity))],
-                    Some(entity),
-                ));
-            }
-            NodeVarient::AudioProd => {
-                info!("inserting pulse");
-                l.push(TChain::vec(
-                    vec![TChain::dsp(Dsp::Output, Some(entity))],
-                    Some(entity),
-                ));
-            }
-            NodeVarient::LuaPulse => panic!("cannot lua pulse to an existing chain."),
-            _ => panic!("not recognized sound node."),
-        },
-        // expected chain type here. creating a new chain.
-        _ => {}
+    if let ChainType::ChainList(ref mut l) = graph.get_chain_mut()[idx].t.as_mut() {
+        l.push(link);
     }
+
+    graph.mark_dirty(idx);
+
+    true
 }
 
+/// Link a node adds to the chain that reached it, none for nodes that can not be linked.
+/// Mixers end the chain with a send into the input the pulse hit.
+pub fn link_chain(name: NodeVarient, entity: Entity, slot_idx: usize) -> Option<TChain> {
+    let dsp = match name {
+        NodeVarient::LuaRead => {
+            info!("inserting read");
+            Dsp::Read(Read)
+        }
+        NodeVarient::Filter(kind) => {
+            info!("inserting filter - {}", kind.name());
+            Dsp::Filter(Filter { kind })
+        }
+        NodeVarient::Envelope => {
+            info!("inserting envelope");
+            Dsp::Envelope(Envelope)
+        }
+        NodeVarient::Delay => {
+            info!("inserting delay");
+            Dsp::Delay(Delay)
+        }
+        NodeVarient::Reverb => {
+            info!("inserting reverb");
+            Dsp::Reverb(Reverb)
+        }
+        NodeVarient::Pan | NodeVarient::AutoPan => {
+            info!("inserting pan");
+            Dsp::Pan(Pan)
+        }
+        NodeVarient::Mixer => {
+            info!("inserting bus send - mixer input {}", slot_idx);
+
+            // the chain ends here, the mixer carries on in its own chain.
+            let send = BusSend {
+                bus: entity,
+                input: slot_idx,
+                node: None,
+            };
+            return Some(TChain::dsp(Dsp::BusSend(send), Some(entity)));
+        }
+        NodeVarient::AudioOut => {
+            info!("inserting audio out");
+            Dsp::Output
+        }
+        _ => return None,
+    };
+
+    Some(TChain::vec(
+        vec![TChain::dsp(dsp, Some(entity))],
+        Some(entity),
+    ))
+}
+
+#[allow(dead_code)]
 pub fn tick_logic(
-    mut commands: Commands,
+    _commands: Commands,
     mut g_query: Query<&mut Grid>,
     config: Res<ConfigAsset>,
     mut query: Query<(Entity, &mut GenericNode, &mut Transform)>,
-    input_node_query: Query<(&Parent, &mut InputSlot)>,
+    _input_node_query: Query<(&Parent, &mut InputSlot)>,
 ) {
-    let box_size = Vec2::new(config.grid_offset_x, config.grid_offset_y);
+    let _box_size = Vec2::new(config.grid_offset_x, config.grid_offset_y);
 
-    if let Some(mut grid) = g_query.iter_mut().next() {
-        query.par_iter_mut().for_each(|(e, mut gn, tform)| {
+    if let Some(_grid) = g_query.iter_mut().next() {
+        query.par_iter_mut().for_each(|(_e, mut gn, _tform)| {
             // Update node-data where necessary (for now just lua nodes)
             match gn.as_ref() {
                 GenericNode::Lua(_) => {
@@ -254,7 +560,7 @@ pub fn tick_logic(
                     //
                     // }
 
-                    let data = gen_node.data.clone();
+                    let _data = gen_node.data.clone();
                 }
             }
         });
@@ -1,7 +1,32 @@
-use bevy::ecs::{event::Event, entity::Entity};
+use bevy::ecs::{entity::Entity, event::Event};
+
+use crate::components::{midi::Note, nodes::types::SlotData};
 
 #[derive(Event)]
 pub struct AudioNodePulseEvent {
     pub entity: Entity,
     pub slot_idx: usize,
 }
+
+/// Sends a pulse carrying `data` out of an output slot, used by nodes that emit values
+/// instead of audio (e.g. the midi player).
+#[derive(Event)]
+pub struct DataPulseEvent {
+    pub entity: Entity,
+    pub slot_idx: usize,
+    pub data: SlotData,
+}
+
+/// A note for the voices of an instrument, sent when a pulse reaches it.
+/// Events are not collapsed like slot data, every pulse of a tick plays.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub enum NoteEvent {
+    /// one shot, without a frequency the instrument plays its frequency slot.
+    Trigger {
+        entity: Entity,
+        frequency: Option<f32>,
+    },
+    /// held until the note off of the key.
+    On { entity: Entity, note: Note },
+    Off { entity: Entity, key: u8 },
+}
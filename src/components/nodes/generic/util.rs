@@ -1,7 +1,7 @@
 use crate::components::nodes::{
     native::NativeNode,
     types::{
-        Node, NodeData, NodeStatus, NodeTrait, NodeType, NodeVarient, ParentNode, Position, Pulse,
+        Node, NodeData, NodeStatus, NodeType, NodeVarient, ParentNode, Position, Pulse,
         SlotData,
     },
 };
@@ -1,7 +1,10 @@
 use bevy::render::color::Color;
 use rlua::{Context, Error, FromLua, Table, ToLua};
 
-use crate::components::nodes::types::NodeVarient;
+use crate::{
+    components::nodes::types::NodeVarient,
+    dsp::{filters::FilterKind, generators::GeneratorKind},
+};
 
 use super::super::types::{
     ColorPair, Node, NodeData, NodeStatus, NodeType, PColor, Position, Slot, SlotData, SlotType,
@@ -12,35 +15,36 @@ impl<'lua> FromLua<'lua> for Node {
     fn from_lua(value: rlua::prelude::LuaValue<'lua>, _ctx: Context<'lua>) -> Result<Self, Error> {
         match value {
             rlua::Value::Table(table) => {
-                let mut node = Node::default();
-                node.name = table.get::<_, NodeVarient>("name")?;
-                node.display = table.get::<_, String>("display")?;
-
-                node.pos = table.get::<_, Position>("pos")?;
-
-                node.active = table.get::<_, ColorPair>("active")?;
-                node.inert = table.get::<_, ColorPair>("inert")?;
-                node.inactive = table.get::<_, ColorPair>("inactive")?;
-
-                node.ntype = table
-                    .get::<_, Table>("ntype")?
-                    .sequence_values::<NodeType>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                node.slots = table
-                    .get::<_, Table>("slots")?
-                    .sequence_values::<Slot>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                node.output_slots = table
-                    .get::<_, Table>("output_slots")?
-                    .sequence_values::<Slot>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                Ok(node)
+                Ok(Node {
+                    name: table.get::<_, NodeVarient>("name")?,
+                    display: table.get::<_, String>("display")?,
+
+                    pos: table.get::<_, Position>("pos")?,
+
+                    active: table.get::<_, ColorPair>("active")?,
+                    inert: table.get::<_, ColorPair>("inert")?,
+                    inactive: table.get::<_, ColorPair>("inactive")?,
+
+                    ntype: table
+                        .get::<_, Table>("ntype")?
+                        .sequence_values::<NodeType>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    slots: table
+                        .get::<_, Table>("slots")?
+                        .sequence_values::<Slot>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    output_slots: table
+                        .get::<_, Table>("output_slots")?
+                        .sequence_values::<Slot>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    asset: table.get::<_, Option<String>>("asset")?,
+                })
             }
             _ => Err(Error::FromLuaConversionError {
                 from: "Node",
@@ -75,7 +79,8 @@ impl<'lua> ToLua<'lua> for Node {
             output_slots.set(i + 1, t.clone())?;
         }
         table.set("output_slots", output_slots)?;
-        Ok(table.to_lua(ctx)?)
+        table.set("asset", self.asset)?;
+        table.to_lua(ctx)
     }
 }
 
@@ -83,12 +88,10 @@ impl<'lua> FromLua<'lua> for ColorPair {
     fn from_lua(value: rlua::prelude::LuaValue<'lua>, _ctx: Context<'lua>) -> Result<Self, Error> {
         match value {
             rlua::Value::Table(table) => {
-                let mut node = ColorPair::default();
-
-                node.foreground = table.get::<_, PColor>("foreground")?;
-                node.background = table.get::<_, PColor>("background")?;
-
-                Ok(node)
+                Ok(ColorPair {
+                    foreground: table.get::<_, PColor>("foreground")?,
+                    background: table.get::<_, PColor>("background")?,
+                })
             }
             _ => Err(Error::FromLuaConversionError {
                 from: "ColorPair",
@@ -104,7 +107,7 @@ impl<'lua> ToLua<'lua> for ColorPair {
         let table = ctx.create_table()?;
         table.set("foreground", self.foreground)?;
         table.set("background", self.background)?;
-        Ok(table.to_lua(ctx)?)
+        table.to_lua(ctx)
     }
 }
 
@@ -135,7 +138,7 @@ impl<'lua> ToLua<'lua> for PColor {
         table.set(1, self.0.r())?;
         table.set(2, self.0.g())?;
         table.set(3, self.0.b())?;
-        Ok(table.to_lua(ctx)?)
+        table.to_lua(ctx)
     }
 }
 
@@ -143,17 +146,15 @@ impl<'lua> FromLua<'lua> for Slot {
     fn from_lua(value: rlua::prelude::LuaValue<'lua>, _ctx: Context<'lua>) -> Result<Self, Error> {
         match value {
             rlua::Value::Table(table) => {
-                let mut slot = Slot::default();
-
-                slot.pos = table.get::<_, Position>("pos")?;
-
-                slot.signal_type = table.get::<_, NodeType>("signal_type")?;
+                Ok(Slot {
+                    pos: table.get::<_, Position>("pos")?,
 
-                slot.slot_type = table.get::<_, SlotType>("slot_type")?;
+                    signal_type: table.get::<_, NodeType>("signal_type")?,
 
-                slot.direction = table.get::<_, Position>("direction")?;
+                    slot_type: table.get::<_, SlotType>("slot_type")?,
 
-                Ok(slot)
+                    direction: table.get::<_, Position>("direction")?,
+                })
             }
             _ => Err(Error::FromLuaConversionError {
                 from: "String",
@@ -170,7 +171,7 @@ impl<'lua> ToLua<'lua> for Slot {
         table.set("pos", self.pos)?;
         table.set("signal_type", self.signal_type)?;
         table.set("slot_type", self.slot_type)?;
-        Ok(table.to_lua(ctx)?)
+        table.to_lua(ctx)
     }
 }
 
@@ -250,31 +251,31 @@ impl<'lua> FromLua<'lua> for NodeData {
     fn from_lua(value: rlua::prelude::LuaValue<'lua>, _ctx: Context<'lua>) -> Result<Self, Error> {
         match value {
             rlua::Value::Table(table) => {
-                let mut node = NodeData::default();
-
-                node.slot_data = table
-                    .get::<_, Table>("slot_data")?
-                    .sequence_values::<SlotData>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                node.output_slot_data = table
-                    .get::<_, Table>("output_slot_data")?
-                    .sequence_values::<SlotData>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                node.updated = table
-                    .get::<_, Table>("updated")?
-                    .sequence_values::<usize>()
-                    .map(|tv| tv.unwrap())
-                    .collect();
-
-                node.state = table.get::<_, NodeStatus>("state")?;
-
-                node.data = table.get::<_, SlotData>("data")?;
-
-                Ok(node)
+                Ok(NodeData {
+                    slot_data: table
+                        .get::<_, Table>("slot_data")?
+                        .sequence_values::<SlotData>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    output_slot_data: table
+                        .get::<_, Table>("output_slot_data")?
+                        .sequence_values::<SlotData>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    updated: table
+                        .get::<_, Table>("updated")?
+                        .sequence_values::<usize>()
+                        .map(|tv| tv.unwrap())
+                        .collect(),
+
+                    state: table.get::<_, NodeStatus>("state")?,
+
+                    data: table.get::<_, SlotData>("data")?,
+
+                    ..Default::default()
+                })
             }
             _ => Err(Error::FromLuaConversionError {
                 from: "NodeData",
@@ -300,11 +301,11 @@ impl<'lua> ToLua<'lua> for NodeData {
         table.set("output_slot_data", output_slot_data)?;
         let updated = ctx.create_table()?;
         for (i, pos) in self.updated.iter().enumerate() {
-            updated.set(i + 1, pos.clone().to_lua(ctx)?)?;
+            updated.set(i + 1, (*pos).to_lua(ctx)?)?;
         }
         table.set("updated", updated)?;
         table.set("state", self.state.to_lua(ctx)?)?;
-        Ok(table.to_lua(ctx)?)
+        table.to_lua(ctx)
     }
 }
 
@@ -340,6 +341,17 @@ impl<'lua> ToLua<'lua> for SlotData {
 
                 Ok(table.to_lua(ctx)?)
             }
+            SlotData::Notes(notes) => {
+                let table = ctx.create_table()?;
+                for (i, note) in notes.iter().enumerate() {
+                    let t = ctx.create_table()?;
+                    t.set("key", note.key)?;
+                    t.set("level", note.level)?;
+                    table.set(i + 1, t)?;
+                }
+
+                Ok(table.to_lua(ctx)?)
+            }
             SlotData::None => Ok(rlua::Value::Nil),
         }
     }
@@ -349,12 +361,10 @@ impl<'lua> FromLua<'lua> for Position {
     fn from_lua(value: rlua::prelude::LuaValue<'lua>, _ctx: Context<'lua>) -> Result<Self, Error> {
         match value {
             rlua::Value::Table(table) => {
-                let mut pos = Position::default();
-
-                pos.x = table.get::<_, i32>("x")?;
-                pos.y = table.get::<_, i32>("y")?;
-
-                Ok(pos)
+                Ok(Position {
+                    x: table.get::<_, i32>("x")?,
+                    y: table.get::<_, i32>("y")?,
+                })
             }
             _ => Err(Error::FromLuaConversionError {
                 from: "String",
@@ -370,7 +380,7 @@ impl<'lua> ToLua<'lua> for Position {
         let table = ctx.create_table()?;
         table.set("x", self.x)?;
         table.set("y", self.y)?;
-        Ok(table.to_lua(ctx)?)
+        table.to_lua(ctx)
     }
 }
 
@@ -410,7 +420,20 @@ impl<'lua> FromLua<'lua> for NodeVarient {
                 "lua_read" => NodeVarient::LuaRead,
                 "lua_pulse" => NodeVarient::LuaPulse,
                 "audio_out" => NodeVarient::AudioOut,
-                s => NodeVarient::Custom(s.to_string()),
+                "sampler" => NodeVarient::Sampler,
+                "wavetable" => NodeVarient::Wavetable,
+                "midi_player" => NodeVarient::MidiPlayer,
+                "envelope" => NodeVarient::Envelope,
+                "delay" => NodeVarient::Delay,
+                "reverb" => NodeVarient::Reverb,
+                "mixer" => NodeVarient::Mixer,
+                "pan" => NodeVarient::Pan,
+                "auto_pan" => NodeVarient::AutoPan,
+                s => match (GeneratorKind::from_name(s), FilterKind::from_name(s)) {
+                    (Some(kind), _) => NodeVarient::Generator(kind),
+                    (_, Some(kind)) => NodeVarient::Filter(kind),
+                    _ => NodeVarient::Custom(s.to_string()),
+                },
             }),
             rlua::Value::Nil => Ok(NodeVarient::None),
             _ => Err(Error::FromLuaConversionError {
@@ -428,6 +451,17 @@ impl<'lua> ToLua<'lua> for NodeVarient {
             NodeVarient::LuaRead => Ok("lua_read".to_lua(ctx)?),
             NodeVarient::LuaPulse => Ok("lua_pulse".to_lua(ctx)?),
             NodeVarient::AudioOut => Ok("audio_out".to_lua(ctx)?),
+            NodeVarient::Generator(kind) => Ok(kind.name().to_lua(ctx)?),
+            NodeVarient::Sampler => Ok("sampler".to_lua(ctx)?),
+            NodeVarient::Wavetable => Ok("wavetable".to_lua(ctx)?),
+            NodeVarient::MidiPlayer => Ok("midi_player".to_lua(ctx)?),
+            NodeVarient::Filter(kind) => Ok(kind.name().to_lua(ctx)?),
+            NodeVarient::Envelope => Ok("envelope".to_lua(ctx)?),
+            NodeVarient::Delay => Ok("delay".to_lua(ctx)?),
+            NodeVarient::Reverb => Ok("reverb".to_lua(ctx)?),
+            NodeVarient::Mixer => Ok("mixer".to_lua(ctx)?),
+            NodeVarient::Pan => Ok("pan".to_lua(ctx)?),
+            NodeVarient::AutoPan => Ok("auto_pan".to_lua(ctx)?),
             NodeVarient::Custom(s) => Ok(s.to_lua(ctx)?),
             NodeVarient::None => Ok(rlua::Value::Nil),
             _ => Ok("None".to_lua(ctx)?),
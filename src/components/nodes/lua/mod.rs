@@ -69,7 +69,7 @@ impl NodeTrait for LuaNode {
     }
 
     fn pos(&self) -> Position {
-        self.node.pos.clone()
+        self.node.pos
     }
 
     fn get_active(&self) -> ColorPair {
@@ -8,6 +8,7 @@ use super::{
     types::{ColorPair, Node, NodeData, NodeTrait, ParentNode, Position, NodeVarient},
 };
 
+#[allow(dead_code)]
 #[derive(Component)]
 pub struct IsNativeNode;
 
@@ -29,7 +30,7 @@ impl NodeTrait for NativeNode {
     }
 
     fn pos(&self) -> Position {
-        self.node.pos.clone()
+        self.node.pos
     }
 
     fn get_active(&self) -> ColorPair {
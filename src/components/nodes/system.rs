@@ -11,20 +11,24 @@ use bevy::{
     input::{keyboard::KeyCode, Input},
     log::info,
 };
-use knyst::{
-    controller::KnystCommands,
-    graph::{connection::InputBundle, GenOrGraph, Graph, GraphSettings},
-    inputs, knyst_commands,
-};
 
 use crate::{
     components::{
+        audio::AudioGraph,
         config::ConfigAsset,
         grid::Grid,
         lua::LuaAsset,
+        midi::{MidiPlayer, DEFAULT_MIDI},
         nodes::{lua::get_lua_wave_handles, types::NodeVarient},
     },
-    dsp::{oscillators::Oscillator, Dsp},
+    dsp::{
+        generators::Generator,
+        mixer::Mixer,
+        oscillators::Oscillator,
+        sampler::{Sampler, DEFAULT_SAMPLE},
+        wavetable::{Wavetable, WavetableSource, DEFAULT_WAVETABLE},
+        Dsp, TChain,
+    },
     lua::init_instance,
 };
 
@@ -36,6 +40,33 @@ use super::{
     util::{create_default_components, spawn_node_with_children},
 };
 
+// temporary key bindings for placing nodes - (key, node name, position).
+const TEMP_NODES: [(KeyCode, &str, (i32, i32)); 23] = [
+    (KeyCode::Space, "lua_pulse", (0, 0)),
+    (KeyCode::A, "lua_read", (0, -5)),
+    (KeyCode::R, "audio_out", (0, -10)),
+    (KeyCode::L, "lowpass", (0, -3)),
+    (KeyCode::H, "highpass", (0, -3)),
+    (KeyCode::B, "bandpass", (0, -3)),
+    (KeyCode::N, "notch", (0, -3)),
+    (KeyCode::E, "envelope", (0, -3)),
+    (KeyCode::D, "delay", (0, -3)),
+    (KeyCode::V, "reverb", (0, -3)),
+    (KeyCode::M, "mixer", (0, -3)),
+    (KeyCode::P, "pan", (0, -3)),
+    (KeyCode::O, "auto_pan", (0, -3)),
+    (KeyCode::Key1, "sine", (-12, 0)),
+    (KeyCode::Key2, "saw", (-8, 0)),
+    (KeyCode::Key3, "square", (-4, 0)),
+    (KeyCode::Key4, "triangle", (4, 0)),
+    (KeyCode::Key5, "pulse", (8, 0)),
+    (KeyCode::Key6, "white_noise", (12, 0)),
+    (KeyCode::Key7, "pink_noise", (16, 0)),
+    (KeyCode::Key8, "sampler", (20, 0)),
+    (KeyCode::Key9, "wavetable", (-16, 0)),
+    (KeyCode::Key0, "midi_player", (-16, 4)),
+];
+
 pub fn keyboard_input_temp(
     config: Res<ConfigAsset>,
     mut commands: Commands,
@@ -44,38 +75,14 @@ pub fn keyboard_input_temp(
     asset_server: Res<AssetServer>,
     lua_assets: Res<Assets<LuaAsset>>,
     keys: Res<Input<KeyCode>>,
+    mut graph: ResMut<AudioGraph>,
     ev_audio_change: EventWriter<AudioNodePulseEvent>,
 ) {
-    if keys.just_pressed(KeyCode::Space) {
-        info!("pressed SPACE");
-        let mut grid = g_query.single_mut();
-        insert_node(
-            &mut grid,
-            config,
-            &mut commands,
-            query,
-            asset_server,
-            lua_assets,
-            "lua_pulse".to_string(),
-            Position::new(0, 0),
-            ev_audio_change,
-        );
-    } else if keys.just_pressed(KeyCode::A) {
-        info!("pressed A");
-        let mut grid = g_query.single_mut();
-        insert_node(
-            &mut grid,
-            config,
-            &mut commands,
-            query,
-            asset_server,
-            lua_assets,
-            "lua_read".to_string(),
-            Position::new(0, -5),
-            ev_audio_change,
-        );
-    } else if keys.just_pressed(KeyCode::R) {
-        info!("pressed R");
+    if let Some((key, name, (x, y))) = TEMP_NODES
+        .iter()
+        .find(|(key, _, _)| keys.just_pressed(*key))
+    {
+        info!("pressed {:?}", key);
         let mut grid = g_query.single_mut();
         insert_node(
             &mut grid,
@@ -84,8 +91,9 @@ pub fn keyboard_input_temp(
             query,
             asset_server,
             lua_assets,
-            "audio_out".to_string(),
-            Position::new(0, -10),
+            &mut graph,
+            name.to_string(),
+            Position::new(*x, *y),
             ev_audio_change,
         );
     }
@@ -98,6 +106,7 @@ pub fn insert_node(
     query: Query<(Entity, &mut GenericNode), (Without<NotSetup>, With<NodeBP>)>,
     asset_server: Res<AssetServer>,
     lua_assets: Res<Assets<LuaAsset>>,
+    graph: &mut AudioGraph,
     name: String,
     pos: Position,
     mut ev_audio_change: EventWriter<AudioNodePulseEvent>,
@@ -165,59 +174,50 @@ pub fn insert_node(
                     &mut output_slots,
                 );
 
+                // midi players do not make sound, they pulse notes into other nodes.
+                if let NodeVarient::MidiPlayer = node.name() {
+                    let path = node
+                        .get_node()
+                        .asset
+                        .clone()
+                        .unwrap_or(DEFAULT_MIDI.to_string());
+
+                    info!("inserting midi player - {}", path);
+
+                    commands.entity(entity).insert(MidiPlayer {
+                        handle: asset_server.load(path),
+                    });
+                }
+
                 let slots = contains_audio(node);
-                slots.iter().for_each(|(i, slot)| {
+                slots.iter().for_each(|(i, _slot)| {
                     info!("contains audio");
 
-                    let mut last_idx = 0;
-
-                    match node.name() {
-                        NodeVarient::LuaPulse => {
-                            info!("inserting pulse");
-
-                            let osc = Oscillator {
-                                lua_handle: get_lua_wave_handles(node),
-                                lua_string: "".to_string(),
-                            };
-
-                            let k = knyst_commands().push(
-                                Graph::new(GraphSettings {
-                                    block_size: 64,
-                                    ..Default::default()
-                                }),
-                                inputs!(),
-                            );
-
-                            let l = graph.get_chain_mut();
-                            l.push(TChain::vec(
-                                vec![TChain::dsp(Dsp::Input(osc), Some(entity))],
-                                Some(entity),
-                            ));
-                            last_idx = l.len() - 1;
-                        }
-                        NodeVarient::AudioOut => {
-                            info!("inserting audio out");
-
-                            let l = graph.get_chain_mut();
-                            l.push(TChain::vec(
-                                vec![TChain::dsp(Dsp::Output, Some(entity))],
-                                Some(entity),
-                            ));
-                            last_idx = l.len() - 1;
+                    let mut last_idx = None;
+
+                    if let Some(dsp) = instrument_dsp(node, &asset_server) {
+                        let l = graph.get_chain_mut();
+                        l.push(TChain::vec(
+                            vec![TChain::dsp(dsp, Some(entity))],
+                            Some(entity),
+                        ));
+                        let idx = l.len() - 1;
+                        last_idx = Some(idx);
+
+                        // mixers are built right away so chains can feed them before they reach an output.
+                        if let NodeVarient::Mixer = node.name() {
+                            graph.mark_dirty(idx);
                         }
-                        _ => (),
                     }
 
-                    node_list.push(AudioNode {
-                        idx: Some(last_idx),
-                    });
+                    node_list.push(AudioNode { idx: last_idx });
                     ev_audio_change.send(AudioNodePulseEvent {
                         entity,
                         slot_idx: i.to_owned(),
                     });
                 });
 
-                if slots.len() > 0 {
+                if !slots.is_empty() {
                     let mut ce = commands.entity(entity);
 
                     node_list.iter().for_each(|item| {
@@ -231,15 +231,68 @@ pub fn insert_node(
     }
 }
 
+/// Dsp of a node that starts its own chain, none for nodes that only link into one.
+pub fn instrument_dsp(node: &NativeNode, asset_server: &AssetServer) -> Option<Dsp> {
+    match node.name() {
+        NodeVarient::LuaPulse => {
+            info!("inserting pulse");
+
+            Some(Dsp::Input(Oscillator {
+                lua_handle: get_lua_wave_handles(node),
+                lua_string: "".to_string(),
+            }))
+        }
+        NodeVarient::Generator(kind) => {
+            info!("inserting generator - {}", kind.name());
+
+            Some(Dsp::Generator(Generator { kind }))
+        }
+        NodeVarient::Sampler => {
+            let path = node
+                .get_node()
+                .asset
+                .clone()
+                .unwrap_or(DEFAULT_SAMPLE.to_string());
+
+            info!("inserting sampler - {}", path);
+
+            Some(Dsp::Sampler(Sampler {
+                handle: asset_server.load(path),
+            }))
+        }
+        NodeVarient::Wavetable => {
+            let path = node
+                .get_node()
+                .asset
+                .clone()
+                .unwrap_or(DEFAULT_WAVETABLE.to_string());
+
+            info!("inserting wavetable - {}", path);
+
+            Some(Dsp::Wavetable(Wavetable {
+                source: WavetableSource::load(asset_server, path),
+            }))
+        }
+        NodeVarient::Mixer => {
+            info!("inserting mixer");
+
+            Some(Dsp::Mixer(Mixer))
+        }
+        NodeVarient::AudioOut => {
+            info!("inserting audio out");
+
+            Some(Dsp::Output)
+        }
+        _ => None,
+    }
+}
+
 fn contains_audio<T: ParentNode>(node: &T) -> Vec<(usize, &Slot)> {
     node.get_node()
         .output_slots
         .iter()
         .enumerate()
-        .filter(|(i, x)| match x.signal_type {
-            NodeType::SignalLink => true,
-            _ => false,
-        })
+        .filter(|(_, x)| matches!(x.signal_type, NodeType::SignalLink))
         .collect()
 }
 
@@ -247,26 +300,26 @@ fn construct_lua_node_from_node_bp(node: &LuaNode, pos: Position) -> LuaNode {
     let mut sub_node = node.node.clone();
     sub_node.pos = pos;
 
-    let lnode = LuaNode {
+    
+
+    LuaNode {
         node: sub_node,
         data: node.data.clone(),
         handles: node.handles.clone(),
         lua: Mutex::new(init_instance()),
-    };
-
-    lnode
+    }
 }
 
-fn construct_native_node_from_node_bp(node: &NativeNode, pos: Position) -> NativeNode {
+pub fn construct_native_node_from_node_bp(node: &NativeNode, pos: Position) -> NativeNode {
     let mut sub_node = node.node.clone();
     sub_node.pos = pos;
 
-    let lnode = NativeNode {
+    
+
+    NativeNode {
         node: sub_node,
         data: node.data.clone(),
         handles: node.handles.clone(),
         lua: Some(Mutex::new(init_instance())),
-    };
-
-    lnode
+    }
 }
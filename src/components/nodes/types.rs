@@ -1,6 +1,10 @@
-use std::sync::Mutex;
+use std::{fmt, sync::Mutex};
 
-use crate::util::{MANTLE, MAROON};
+use crate::{
+    components::midi::Note,
+    dsp::{filters::FilterKind, generators::GeneratorKind},
+    util::{MANTLE, MAROON},
+};
 use bevy::{
     ecs::{component::Component, entity::Entity},
     math::Vec2,
@@ -23,6 +27,7 @@ pub struct ColorPair {
 }
 
 impl ColorPair {
+    #[allow(dead_code)]
     pub fn new(fg: Color, bg: Color) -> Self {
         Self {
             foreground: PColor(fg),
@@ -44,8 +49,10 @@ pub trait NodeTrait {
     fn name(&self) -> NodeVarient;
     fn display(&self) -> String;
     fn pos(&self) -> Position;
+    #[allow(dead_code)]
     fn get_active(&self) -> ColorPair;
     fn get_inert(&self) -> ColorPair;
+    #[allow(dead_code)]
     fn get_inactive(&self) -> ColorPair;
 }
 
@@ -56,8 +63,10 @@ pub trait ParentNode {
     fn get_data_mut(&mut self) -> &mut NodeData;
     fn get_node_mut(&mut self) -> &mut Node;
     fn get_lua(&self) -> Option<&Mutex<Lua>>;
+    #[allow(dead_code)]
     fn get_lua_mut(&mut self) -> Option<&mut Mutex<Lua>>;
     fn get_lua_handles(&self) -> Option<&Vec<LuaHandle>>;
+    #[allow(dead_code)]
     fn get_lua_handles_mut(&mut self) -> Option<&mut Vec<LuaHandle>>;
 }
 
@@ -86,6 +95,7 @@ pub struct AudioNode {
 
 #[derive(Debug, Clone, Component)]
 pub struct OutputSlot {
+    #[allow(dead_code)]
     pub idx: usize,
     pub slot: SlotNode,
 }
@@ -100,7 +110,7 @@ impl NodeTrait for OutputSlot {
     }
 
     fn pos(&self) -> Position {
-        self.slot.pos.clone()
+        self.slot.pos
     }
 
     fn get_active(&self) -> ColorPair {
@@ -132,7 +142,7 @@ impl NodeTrait for InputSlot {
     }
 
     fn pos(&self) -> Position {
-        self.slot.pos.clone()
+        self.slot.pos
     }
 
     fn get_active(&self) -> ColorPair {
@@ -161,11 +171,11 @@ impl Position {
         Self { x, y }
     }
 
-    pub fn to_vec2(&self) -> Vec2 {
+    pub fn to_vec2(self) -> Vec2 {
         Vec2::new(self.x as f32, self.y as f32)
     }
 
-    pub fn to_tuple(&self) -> (i32, i32) {
+    pub fn to_tuple(self) -> (i32, i32) {
         (self.x, self.y)
     }
 
@@ -186,13 +196,17 @@ pub struct Slot {
 
 #[derive(Component, Clone, Debug)]
 pub struct SlotNode {
+    #[allow(dead_code)]
     pub slot_type: SlotType,
+    #[allow(dead_code)]
     pub signal_type: NodeType,
     pub pos: Position,
     pub display: String,
     pub name: NodeVarient,
+    #[allow(dead_code)]
     pub active: ColorPair,
     pub inert: ColorPair,
+    #[allow(dead_code)]
     pub inactive: ColorPair,
 }
 
@@ -211,6 +225,9 @@ pub struct Node {
     pub ntype: Vec<NodeType>,
     pub slots: Vec<Slot>,
     pub output_slots: Vec<Slot>,
+
+    // file the node plays (sample, wavetable or midi file), relative to the assets folder.
+    pub asset: Option<String>,
 }
 
 /// Data object for the node - All nodes should have this struct.
@@ -223,11 +240,24 @@ pub struct NodeData {
     pub updated: Vec<usize>,
     pub state: NodeStatus,
 
+    #[allow(dead_code)]
     pub commands: Vec<NodeCommand>,
 }
 
+impl NodeData {
+    /// value of an F32 input slot.
+    pub fn get_f32(&self, idx: usize) -> Option<f32> {
+        match self.slot_data.get(idx) {
+            Some(SlotData::F32(f)) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum NodeCommand {
+    #[allow(dead_code)]
     SpawnNode(Node),
     #[default]
     None,
@@ -281,6 +311,8 @@ pub enum SlotData {
     F32x2((f32, f32)),
 
     Bang(bool),
+    // only carried by pulses, a slot never holds notes.
+    Notes(Vec<Note>),
     #[default]
     None,
 }
@@ -288,6 +320,7 @@ pub enum SlotData {
 #[derive(Clone, Debug)]
 pub enum ChannelType {
     Instrument,
+    Generator,
     Transmitter,
     Terminator,
 }
@@ -298,20 +331,46 @@ pub enum NodeVarient {
     LuaRead,
     AudioOut,
     AudioProd,
+    DataProd,
+    Generator(GeneratorKind),
+    Sampler,
+    Wavetable,
+    MidiPlayer,
+    Filter(FilterKind),
+    Envelope,
+    Delay,
+    Reverb,
+    Mixer,
+    Pan,
+    AutoPan,
     Custom(String),
     #[default]
     None,
 }
 
-impl NodeVarient {
-    pub fn to_string(&self) -> String {
-        match self {
-            NodeVarient::LuaPulse => "lua_pulse".to_string(),
-            NodeVarient::LuaRead => "lua_read".to_string(),
-            NodeVarient::AudioOut => "audio_out".to_string(),
-            NodeVarient::AudioProd => "audio_prod".to_string(),
-            NodeVarient::Custom(s) => s.to_string(),
-            NodeVarient::None => "none".to_string(),
-        }
+impl fmt::Display for NodeVarient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            NodeVarient::LuaPulse => "lua_pulse",
+            NodeVarient::LuaRead => "lua_read",
+            NodeVarient::AudioOut => "audio_out",
+            NodeVarient::AudioProd => "audio_prod",
+            NodeVarient::DataProd => "data_prod",
+            NodeVarient::Generator(kind) => kind.name(),
+            NodeVarient::Filter(kind) => kind.name(),
+            NodeVarient::Envelope => "envelope",
+            NodeVarient::Delay => "delay",
+            NodeVarient::Reverb => "reverb",
+            NodeVarient::Mixer => "mixer",
+            NodeVarient::Pan => "pan",
+            NodeVarient::AutoPan => "auto_pan",
+            NodeVarient::Sampler => "sampler",
+            NodeVarient::Wavetable => "wavetable",
+            NodeVarient::MidiPlayer => "midi_player",
+            NodeVarient::Custom(s) => s,
+            NodeVarient::None => "none",
+        };
+
+        write!(f, "{}", name)
     }
 }
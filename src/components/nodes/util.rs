@@ -14,7 +14,7 @@ use bevy::{
     render::{color::Color, view::RenderLayers},
     sprite::{Sprite, SpriteBundle},
     text::{Text, Text2dBounds, Text2dBundle, TextAlignment, TextSection, TextStyle},
-    transform::components::{GlobalTransform, Transform},
+    transform::components::Transform,
 };
 
 use crate::{
@@ -128,7 +128,7 @@ pub fn spawn_node_with_text<T: NodeTrait + Component>(
 
     grid.add_to_grid(ce.id(), node_pos.to_tuple());
 
-    return ce.id();
+    ce.id()
 }
 
 pub fn spawn_child_node_with_text<T: NodeTrait + Component>(
@@ -164,7 +164,7 @@ pub fn spawn_child_node_with_text<T: NodeTrait + Component>(
     info!("inserting into grid: {:?}", node_pos);
     grid.add_to_grid(ce.id(), node_pos.to_tuple());
 
-    return ce.id();
+    ce.id()
 }
 
 pub fn spawn_node<T: NodeTrait + Component>(config: &Res<ConfigAsset>, node: T) -> impl Bundle {
@@ -204,7 +204,7 @@ pub fn create_default_components<T: NodeTrait + ParentNode + Component>(
                     signal_type: os.signal_type.clone(),
                     display: get_slot_name(true, &os.slot_type),
                     name: NodeVarient::Custom(get_slot_name(true, &os.slot_type)), // TODO: get actual name
-                    pos: os.pos.clone(),
+                    pos: os.pos,
                     active: slot_active(),
                     inert: slot_inert(),
                     inactive: slot_inactive(),
@@ -220,7 +220,7 @@ pub fn create_default_components<T: NodeTrait + ParentNode + Component>(
                 signal_type: os.signal_type.clone(),
                 display: get_slot_name(false, &os.slot_type),
                 name: NodeVarient::Custom(get_slot_name(false, &os.slot_type)), // TODO: get actual name
-                pos: os.pos.clone(),
+                pos: os.pos,
                 active: slot_active(),
                 inert: slot_inert(),
                 inactive: slot_inactive(),
@@ -228,7 +228,7 @@ pub fn create_default_components<T: NodeTrait + ParentNode + Component>(
         });
     });
 
-    return (c, s, t);
+    (c, s, t)
 }
 
 pub fn get_slot_name(is_output: bool, slot_type: &SlotType) -> String {
@@ -0,0 +1,102 @@
+use std::net::UdpSocket;
+
+use bevy::{
+    ecs::system::{Res, ResMut, Resource},
+    log::{info, warn},
+};
+use rosc::{encoder, OscMessage, OscPacket, OscType};
+
+use crate::components::{config::ConfigAsset, nodes::types::Position};
+
+/// What happened to a pulse, each has its own address.
+#[derive(Clone, Copy, Debug)]
+pub enum PulseOsc {
+    /// left an output slot.
+    Spawn,
+    /// moved one cell.
+    Move,
+    /// reached an input slot.
+    Hit,
+}
+
+impl PulseOsc {
+    fn addr(&self) -> &'static str {
+        match self {
+            PulseOsc::Spawn => "/pulse/spawn",
+            PulseOsc::Move => "/pulse/move",
+            PulseOsc::Hit => "/pulse/hit",
+        }
+    }
+}
+
+/// Sends pulse events to the osc target of the config, an empty target turns it off.
+#[derive(Resource, Default)]
+pub struct OscClient {
+    socket: Option<UdpSocket>,
+    target: String,
+}
+
+impl OscClient {
+    fn connect(&mut self, target: &str) {
+        if self.target == target {
+            return;
+        }
+
+        self.target = target.to_string();
+        self.socket = None;
+
+        if target.is_empty() {
+            info!("osc output off");
+            return;
+        }
+
+        let socket = UdpSocket::bind("0.0.0.0:0").and_then(|socket| {
+            socket.connect(target)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+
+        match socket {
+            Ok(socket) => {
+                info!("sending osc to {}", target);
+                self.socket = Some(socket);
+            }
+            Err(err) => warn!("cannot send osc to {} - {}", target, err),
+        }
+    }
+
+    pub fn send(&self, addr: &str, args: Vec<OscType>) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        });
+
+        // nobody listening on the target is fine, messages are fire and forget.
+        if let Ok(buf) = encoder::encode(&packet) {
+            let _ = socket.send(&buf);
+        }
+    }
+
+    /// `/pulse/<spawn|move|hit> x y name slot` - the node is the emitter of the pulse,
+    /// or the node that was hit together with its input slot.
+    pub fn pulse(&self, event: PulseOsc, pos: Position, name: String, slot_idx: usize) {
+        self.send(
+            event.addr(),
+            vec![
+                OscType::Int(pos.x),
+                OscType::Int(pos.y),
+                OscType::String(name),
+                OscType::Int(slot_idx as i32),
+            ],
+        );
+    }
+}
+
+/// (re)connects when the target in the config changes.
+pub fn connect_osc_client(config: Res<ConfigAsset>, mut client: ResMut<OscClient>) {
+    client.connect(&config.osc_target);
+}
@@ -0,0 +1,40 @@
+//! Open Sound Control - remote control of the grid and the transport over udp.
+
+pub mod client;
+pub mod server;
+
+use bevy::{
+    ecs::schedule::common_conditions::resource_changed,
+    prelude::{App, IntoSystemConfigs, Plugin, Update},
+};
+
+use crate::components::config::ConfigAsset;
+
+use self::{
+    client::{connect_osc_client, OscClient},
+    server::{bind_osc_server, receive_osc, OscServer},
+};
+
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+pub const DEFAULT_OSC_TARGET: &str = "127.0.0.1:9001";
+
+pub struct OscPlugin;
+
+impl Plugin for OscPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OscServer>()
+            .init_resource::<OscClient>()
+            .add_systems(
+                Update,
+                (
+                    bind_osc_server.run_if(resource_changed::<ConfigAsset>()),
+                    receive_osc,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                connect_osc_client.run_if(resource_changed::<ConfigAsset>()),
+            );
+    }
+}
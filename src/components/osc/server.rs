@@ -0,0 +1,264 @@
+use std::net::UdpSocket;
+
+use bevy::{
+    ecs::{
+        query::Without,
+        system::{Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+};
+use rosc::{decoder, OscMessage, OscPacket, OscType};
+
+use crate::components::{
+    config::ConfigAsset,
+    grid::Grid,
+    nodes::{
+        generic::GenericNode,
+        types::{NodeBP, ParentNode, Pulse, SlotData},
+    },
+    transport::Transport,
+};
+
+/// Listens for osc messages on localhost, the socket never blocks a frame.
+/// Port `0` in the config turns the server off.
+#[derive(Resource, Default)]
+pub struct OscServer {
+    socket: Option<UdpSocket>,
+    port: u16,
+}
+
+impl OscServer {
+    fn bind(&mut self, port: u16) {
+        if self.port == port && (self.socket.is_some() || port == 0) {
+            return;
+        }
+
+        self.port = port;
+        self.socket = None;
+
+        if port == 0 {
+            info!("osc server off");
+            return;
+        }
+
+        let socket = UdpSocket::bind(("127.0.0.1", port)).and_then(|socket| {
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+
+        match socket {
+            Ok(socket) => {
+                info!("osc server listening on 127.0.0.1:{}", port);
+                self.socket = Some(socket);
+            }
+            Err(err) => warn!("cannot start osc server on port {} - {}", port, err),
+        }
+    }
+
+    /// every message received since the last call, bundles are flattened.
+    fn receive(&self) -> Vec<OscMessage> {
+        let mut messages = vec![];
+
+        let Some(socket) = &self.socket else {
+            return messages;
+        };
+
+        let mut buf = [0u8; decoder::MTU];
+        while let Ok(size) = socket.recv(&mut buf) {
+            match decoder::decode_udp(&buf[..size]) {
+                Ok((_, packet)) => flatten(packet, &mut messages),
+                Err(err) => warn!("cannot decode osc packet - {:?}", err),
+            }
+        }
+
+        messages
+    }
+}
+
+fn flatten(packet: OscPacket, messages: &mut Vec<OscMessage>) {
+    match packet {
+        OscPacket::Message(message) => messages.push(message),
+        OscPacket::Bundle(bundle) => bundle
+            .content
+            .into_iter()
+            .for_each(|packet| flatten(packet, messages)),
+    }
+}
+
+/// Addresses the server understands.
+#[derive(Debug)]
+enum OscCommand {
+    /// `/node/<x>/<y>/slot/<i>` - writes an input slot of the node at a grid position.
+    Slot { pos: (i32, i32), idx: usize },
+    /// `/transport/bpm` - sets the tempo.
+    Bpm,
+}
+
+impl OscCommand {
+    fn parse(addr: &str) -> Option<Self> {
+        let parts = addr
+            .trim_start_matches('/')
+            .split('/')
+            .collect::<Vec<&str>>();
+
+        match parts.as_slice() {
+            ["node", x, y, "slot", idx] => Some(OscCommand::Slot {
+                pos: (x.parse().ok()?, y.parse().ok()?),
+                idx: idx.parse().ok()?,
+            }),
+            ["transport", "bpm"] => Some(OscCommand::Bpm),
+            _ => None,
+        }
+    }
+}
+
+/// (re)binds the socket when the port in the config changes.
+pub fn bind_osc_server(config: Res<ConfigAsset>, mut server: ResMut<OscServer>) {
+    server.bind(config.osc_port);
+}
+
+pub fn receive_osc(
+    server: Res<OscServer>,
+    g_query: Query<&Grid>,
+    mut query: Query<&mut GenericNode, (Without<NodeBP>, Without<Pulse>)>,
+    mut transport: ResMut<Transport>,
+) {
+    for message in server.receive() {
+        match OscCommand::parse(&message.addr) {
+            Some(OscCommand::Slot { pos, idx }) => {
+                let Some(entity) = g_query
+                    .get_single()
+                    .ok()
+                    .and_then(|grid| grid.get_entity(pos))
+                else {
+                    warn!("osc - no node at {:?}", pos);
+                    continue;
+                };
+
+                // slots of a node sit on the grid as well, only the node itself takes writes.
+                let Ok(mut gnode) = query.get_mut(entity) else {
+                    warn!("osc - no node at {:?}", pos);
+                    continue;
+                };
+
+                // read first so a message that does not fit leaves the node unchanged.
+                let Some(slot) = gnode.get_data().slot_data.get(idx) else {
+                    warn!("osc - node at {:?} has no slot {}", pos, idx);
+                    continue;
+                };
+
+                match to_slot_data(slot, &message.args) {
+                    Some(data) => gnode.get_data_mut().slot_data[idx] = data,
+                    None => warn!(
+                        "osc - {:?} does not fit slot {} of node at {:?}",
+                        message.args, idx, pos
+                    ),
+                }
+            }
+            Some(OscCommand::Bpm) => match message.args.first().and_then(to_f32) {
+                Some(bpm) => transport.set_bpm(bpm),
+                None => warn!("osc - /transport/bpm expects a number"),
+            },
+            None => warn!("osc - unknown address {}", message.addr),
+        }
+    }
+}
+
+/// converts arguments into the type the slot already holds.
+fn to_slot_data(slot: &SlotData, args: &[OscType]) -> Option<SlotData> {
+    match slot {
+        SlotData::F32(_) => Some(SlotData::F32(to_f32(args.first()?)?)),
+        SlotData::I32(_) => Some(SlotData::I32(to_f32(args.first()?)?.round() as i32)),
+        SlotData::F32x2(_) => Some(SlotData::F32x2((
+            to_f32(args.first()?)?,
+            to_f32(args.get(1)?)?,
+        ))),
+        // a message without arguments is a bang as well.
+        SlotData::Bang(_) => Some(SlotData::Bang(match args.first() {
+            Some(OscType::Bool(b)) => *b,
+            Some(arg) => to_f32(arg)? > 0.0,
+            None => true,
+        })),
+        SlotData::Notes(_) | SlotData::None => None,
+    }
+}
+
+fn to_f32(arg: &OscType) -> Option<f32> {
+    match arg {
+        OscType::Float(f) => Some(*f),
+        OscType::Double(d) => Some(*d as f32),
+        OscType::Int(i) => Some(*i as f32),
+        OscType::Long(l) => Some(*l as f32),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rosc::OscType;
+
+    use crate::components::nodes::types::SlotData;
+
+    use super::{to_slot_data, OscCommand};
+
+    #[test]
+    fn parses_addresses() {
+        assert!(matches!(
+            OscCommand::parse("/node/3/-2/slot/1"),
+            Some(OscCommand::Slot {
+                pos: (3, -2),
+                idx: 1
+            })
+        ));
+        assert!(matches!(
+            OscCommand::parse("/transport/bpm"),
+            Some(OscCommand::Bpm)
+        ));
+
+        assert!(OscCommand::parse("/node/3/x/slot/1").is_none());
+        assert!(OscCommand::parse("/node/3/2/slot/-1").is_none());
+        assert!(OscCommand::parse("/node/3/2/slot").is_none());
+        assert!(OscCommand::parse("/transport/swing").is_none());
+    }
+
+    #[test]
+    fn args_take_the_type_of_the_slot() {
+        assert!(matches!(
+            to_slot_data(&SlotData::F32(0.0), &[OscType::Int(3)]),
+            Some(SlotData::F32(f)) if f == 3.0
+        ));
+        assert!(matches!(
+            to_slot_data(&SlotData::I32(0), &[OscType::Float(2.6)]),
+            Some(SlotData::I32(3))
+        ));
+        assert!(matches!(
+            to_slot_data(
+                &SlotData::F32x2((0.0, 0.0)),
+                &[OscType::Double(0.5), OscType::Float(-0.5)]
+            ),
+            Some(SlotData::F32x2((l, r))) if l == 0.5 && r == -0.5
+        ));
+    }
+
+    #[test]
+    fn bangs_and_mismatches() {
+        assert!(matches!(
+            to_slot_data(&SlotData::Bang(false), &[]),
+            Some(SlotData::Bang(true))
+        ));
+        assert!(matches!(
+            to_slot_data(&SlotData::Bang(true), &[OscType::Bool(false)]),
+            Some(SlotData::Bang(false))
+        ));
+        assert!(matches!(
+            to_slot_data(&SlotData::Bang(false), &[OscType::Int(0)]),
+            Some(SlotData::Bang(false))
+        ));
+
+        // missing or non numeric arguments leave the slot alone.
+        assert!(to_slot_data(&SlotData::F32(0.0), &[]).is_none());
+        assert!(to_slot_data(&SlotData::F32x2((0.0, 0.0)), &[OscType::Float(1.0)]).is_none());
+        assert!(to_slot_data(&SlotData::F32(0.0), &[OscType::String("a".into())]).is_none());
+        assert!(to_slot_data(&SlotData::None, &[OscType::Float(1.0)]).is_none());
+    }
+}
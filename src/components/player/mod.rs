@@ -3,9 +3,11 @@ use bevy::{
     sprite::SpriteBundle,
 };
 
+#[allow(dead_code)]
 #[derive(Component, Default, Clone)]
 pub struct Player;
 
+#[allow(dead_code)]
 #[derive(Bundle, Default, Clone)]
 pub struct PlayerBundle {
     pub player: Player,
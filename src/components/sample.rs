@@ -0,0 +1,123 @@
+//! Implements loader for audio samples, decoded up front into stereo frames.
+
+use std::{io::Cursor, sync::Arc};
+
+use bevy::{
+    asset::{io::Reader, Asset, AssetLoader, AsyncReadExt, LoadContext},
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as DecodeError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+use thiserror::Error;
+
+/// Decoded sample, mono files are duplicated onto both channels.
+/// Frames are shared with every stream playing the sample.
+#[derive(Asset, TypePath)]
+pub struct SampleAsset {
+    pub sample_rate: f32,
+    pub frames: Arc<Vec<(f32, f32)>>,
+}
+
+#[derive(Default)]
+pub struct SampleLoader;
+
+impl AssetLoader for SampleLoader {
+    type Asset = SampleAsset;
+    type Settings = ();
+    type Error = SampleLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+
+            let mut hint = Hint::new();
+            if let Some(extension) = load_context.path().extension().and_then(|e| e.to_str()) {
+                hint.with_extension(extension);
+            }
+
+            decode(bytes, hint)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wav", "flac"]
+    }
+}
+
+fn decode(bytes: Vec<u8>, hint: Hint) -> Result<SampleAsset, SampleLoaderError> {
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?
+        .format;
+
+    let track = format.default_track().ok_or(SampleLoaderError::NoTrack)?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100) as f32;
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut frames = vec![];
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // end of the stream.
+            Err(DecodeError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = decoder.decode(&packet)?;
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+
+        frames.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| (frame[0], *frame.get(1).unwrap_or(&frame[0]))),
+        );
+    }
+
+    Ok(SampleAsset {
+        sample_rate,
+        frames: Arc::new(frames),
+    })
+}
+
+/// Possible errors that can be produced by [`SampleLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SampleLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file could not be decoded
+    #[error("Could not decode sample: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("Sample has no audio track")]
+    NoTrack,
+}
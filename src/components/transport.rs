@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::{
+        schedule::common_conditions::resource_changed,
+        system::{Res, ResMut, Resource},
+    },
+    input::{keyboard::KeyCode, Input},
+    log::info,
+    prelude::{App, FixedUpdate, IntoSystemConfigs, Plugin, Update},
+    time::{Fixed, Time},
+};
+
+use super::config::ConfigAsset;
+
+pub const DEFAULT_BPM: f32 = 120.0;
+pub const DEFAULT_TICKS_PER_BEAT: u32 = 8;
+pub const DEFAULT_BEATS_PER_BAR: u32 = 4;
+
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 400.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TransportState {
+    #[default]
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// Transport - the clock pulses move on.
+/// Every tick of the fixed timestep moves pulses one cell, its length follows the tempo.
+#[derive(Resource, Clone, Debug)]
+pub struct Transport {
+    bpm: f32,
+    ticks_per_beat: u32,
+    beats_per_bar: u32,
+    // 0.0 is straight, 1.0 plays every pair of ticks as a triplet.
+    swing: f32,
+
+    state: TransportState,
+    // ticks since the transport was last stopped.
+    tick: u64,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self {
+            bpm: DEFAULT_BPM,
+            ticks_per_beat: DEFAULT_TICKS_PER_BEAT,
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            swing: 0.0,
+
+            state: TransportState::default(),
+            tick: 0,
+        }
+    }
+}
+
+impl Transport {
+    pub fn from_config(config: &ConfigAsset) -> Self {
+        let mut transport = Self::default();
+        transport.apply_config(config);
+
+        transport
+    }
+
+    pub fn apply_config(&mut self, config: &ConfigAsset) {
+        self.set_bpm(config.bpm);
+        self.set_ticks_per_beat(config.ticks_per_beat);
+        self.set_beats_per_bar(config.beats_per_bar);
+        self.set_swing(config.swing);
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+    }
+
+    pub fn ticks_per_beat(&self) -> u32 {
+        self.ticks_per_beat
+    }
+
+    pub fn set_ticks_per_beat(&mut self, ticks: u32) {
+        self.ticks_per_beat = ticks.max(1);
+    }
+
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar
+    }
+
+    pub fn set_beats_per_bar(&mut self, beats: u32) {
+        self.beats_per_bar = beats.max(1);
+    }
+
+    pub fn set_swing(&mut self, swing: f32) {
+        self.swing = swing.clamp(0.0, 1.0);
+    }
+
+    pub fn state(&self) -> TransportState {
+        self.state
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state == TransportState::Playing
+    }
+
+    pub fn play(&mut self) {
+        self.state = TransportState::Playing;
+    }
+
+    pub fn pause(&mut self) {
+        self.state = TransportState::Paused;
+    }
+
+    /// pauses and rewinds to the first bar.
+    pub fn stop(&mut self) {
+        self.state = TransportState::Stopped;
+        self.tick = 0;
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// bar of the current tick, counted from 1.
+    pub fn bar(&self) -> u64 {
+        self.tick / self.ticks_per_bar() + 1
+    }
+
+    /// beat within the current bar, counted from 1.
+    pub fn beat(&self) -> u64 {
+        (self.tick / self.ticks_per_beat as u64) % self.beats_per_bar as u64 + 1
+    }
+
+    fn ticks_per_bar(&self) -> u64 {
+        (self.ticks_per_beat * self.beats_per_bar) as u64
+    }
+
+    /// length of a tick without swing, in seconds.
+    pub fn tick_seconds(&self) -> f32 {
+        60.0 / self.bpm / self.ticks_per_beat as f32
+    }
+
+    /// length of the current tick, swing stretches even ticks and shortens odd ones.
+    pub fn tick_duration(&self) -> Duration {
+        let swing = self.swing / 3.0;
+
+        let scale = match self.tick % 2 {
+            0 => 1.0 + swing,
+            _ => 1.0 - swing,
+        };
+
+        Duration::from_secs_f32(self.tick_seconds() * scale)
+    }
+
+    fn advance(&mut self) {
+        self.tick += 1;
+    }
+}
+
+pub struct TransportPlugin;
+
+impl Plugin for TransportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Transport>()
+            .add_systems(FixedUpdate, advance_transport)
+            .add_systems(
+                Update,
+                (
+                    transport_keys,
+                    update_transport_config.run_if(resource_changed::<ConfigAsset>()),
+                ),
+            );
+    }
+}
+
+/// run condition for systems that move with the transport.
+pub fn transport_playing(transport: Res<Transport>) -> bool {
+    transport.is_playing()
+}
+
+/// Counts ticks and sets the length of the next one on the fixed clock.
+pub fn advance_transport(mut transport: ResMut<Transport>, mut time: ResMut<Time<Fixed>>) {
+    if transport.is_playing() {
+        transport.advance();
+    }
+
+    let duration = transport.tick_duration();
+    if time.timestep() != duration {
+        time.set_timestep(duration);
+    }
+}
+
+// temporary key bindings - enter plays and pauses, backspace stops, up and down change the tempo.
+fn transport_keys(keys: Res<Input<KeyCode>>, mut transport: ResMut<Transport>) {
+    if keys.just_pressed(KeyCode::Return) {
+        match transport.is_playing() {
+            true => transport.pause(),
+            false => transport.play(),
+        }
+        info!(
+            "transport {:?} at bar {} beat {}",
+            transport.state(),
+            transport.bar(),
+            transport.beat()
+        );
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        transport.stop();
+        info!("transport {:?}", transport.state());
+    }
+
+    let step = match keys.pressed(KeyCode::ShiftLeft) {
+        true => 10.0,
+        false => 1.0,
+    };
+
+    if keys.just_pressed(KeyCode::Up) {
+        let bpm = transport.bpm() + step;
+        transport.set_bpm(bpm);
+        info!("bpm {}", transport.bpm());
+    }
+
+    if keys.just_pressed(KeyCode::Down) {
+        let bpm = transport.bpm() - step;
+        transport.set_bpm(bpm);
+        info!("bpm {}", transport.bpm());
+    }
+}
+
+/// tempo settings from the config file, reapplied when it is saved.
+fn update_transport_config(config: Res<ConfigAsset>, mut transport: ResMut<Transport>) {
+    transport.apply_config(&config);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Transport;
+
+    fn transport(bpm: f32, ticks_per_beat: u32, swing: f32) -> Transport {
+        let mut transport = Transport::default();
+        transport.set_bpm(bpm);
+        transport.set_ticks_per_beat(ticks_per_beat);
+        transport.set_swing(swing);
+
+        transport
+    }
+
+    fn assert_close(a: Duration, b: Duration) {
+        let diff = a.as_secs_f64() - b.as_secs_f64();
+        assert!(diff.abs() < 1e-6, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn tick_duration_follows_tempo() {
+        let transport = transport(120.0, 8, 0.0);
+
+        // 120 bpm is half a second per beat, split into 8 ticks.
+        assert!((transport.tick_seconds() - 0.0625).abs() < 1e-6);
+        assert_close(transport.tick_duration(), Duration::from_secs_f32(0.0625));
+    }
+
+    #[test]
+    fn tempo_and_ticks_are_clamped() {
+        let transport = transport(1000.0, 0, 0.0);
+
+        assert_eq!(transport.bpm(), 400.0);
+        assert!((transport.tick_seconds() - 0.15).abs() < 1e-6);
+    }
+
+    #[test]
+    fn swing_keeps_the_length_of_a_pair() {
+        let mut transport = transport(120.0, 4, 1.0);
+
+        let even = transport.tick_duration();
+        transport.advance();
+        let odd = transport.tick_duration();
+
+        // full swing plays a pair of ticks as a 2:1 triplet.
+        assert!(even > odd);
+        assert_close(even + odd, Duration::from_secs_f32(transport.tick_seconds() * 2.0));
+        assert!((even.as_secs_f32() / odd.as_secs_f32() - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stop_rewinds_to_the_first_bar() {
+        let mut transport = transport(120.0, 2, 0.0);
+
+        (0..9).for_each(|_| transport.advance());
+        assert_eq!((transport.bar(), transport.beat()), (2, 1));
+
+        transport.stop();
+        assert!(!transport.is_playing());
+        assert_eq!((transport.bar(), transport.beat()), (1, 1));
+    }
+}
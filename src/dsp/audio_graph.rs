@@ -1,93 +1,298 @@
 pub const AUDIO_BUFFER: usize = AUDIO_SIZE * 256;
 pub const AUDIO_SIZE: usize = 64;
 
+use std::sync::Arc;
+
 use bevy::{
-    app::{PostUpdate, Update},
+    app::{AppExit, Last, PostUpdate, Update},
     asset::{AssetEvent, Assets},
-    ecs::{component::TableStorage, event::EventReader, query::With, system::Res},
-    prelude::{
-        App, Commands, Component, Deref, DerefMut, Entity, NonSendMut, Plugin, Query, Without,
+    ecs::{
+        component::TableStorage,
+        event::EventReader,
+        schedule::{
+            common_conditions::{not, resource_exists},
+            IntoSystemConfigs,
+        },
+        system::{Query, Res, SystemParam},
+        world::{FromWorld, World},
     },
+    log::{error, info, warn},
+    prelude::{App, Commands, Component, Deref, DerefMut, Entity, Plugin, ResMut},
 };
 
 use knyst::{
     audio_backend::{CpalBackend, CpalBackendOptions},
     controller::KnystCommands,
     gen::Gen,
-    graph::{connection::InputBundle, Graph, GraphSettings, NodeId},
+    graph::NodeId,
     inputs, knyst_commands,
     sphere::{KnystSphere, SphereSettings},
+    KnystError,
 };
 
 use crate::{
-    components::lua::LuaAsset,
+    components::{
+        audio::{
+            system::{
+                build_audio_chains, collect_wave_errors, play_notes, update_delays,
+                update_envelopes, update_filters, update_generators, update_mixers,
+                update_oscillators, update_pans, update_reverbs, update_samplers,
+                update_wavetables,
+            },
+            AudioGraph, WaveErrors,
+        },
+        config::ConfigAsset,
+        lua::LuaAsset,
+        nodes::generic::types::NoteEvent,
+        sample::SampleAsset,
+    },
     dsp::{
-        oscillators::Oscillator, read::Read, AudioControl as AC, AudioSend, AudioSendControl,
-        Chain, Dsp,
+        backend::{
+            finalize_audio_sink, tick_audio_clock, AudioBackendKind, Backend, BackendSettings,
+            OfflineBackend, OFFLINE_SAMPLE_RATE,
+        },
+        delay::Delay,
+        envelope::Envelope,
+        filters::Filter,
+        generators::Generator,
+        mixer::Mixer,
+        oscillators::Oscillator,
+        pan::Pan,
+        profiler::{DspLoad, Profiled},
+        read::Read,
+        realtime::{report_realtime_violations, Realtime, RealtimeReport},
+        render::{insert_render_patch, render_offline, BitDepth, RenderSettings, WavSink},
+        reverb::Reverb,
+        sampler::Sampler,
+        wavetable::Wavetable,
+        AudioControl as AC, AudioSend, AudioSendControl, Dsp,
     },
 };
 
-pub struct AudioPlugin;
+#[derive(Default)]
+pub struct AudioPlugin {
+    pub render: Option<RenderSettings>,
+    pub backend: BackendSettings,
+}
 
 impl Plugin for AudioPlugin {
     fn build(&self, app: &mut App) {
-        app.init_non_send_resource::<AudioOutput>()
-            .add_systems(PostUpdate, play_audio)
-            .add_systems(Update, update_audio);
+        if let Some(render) = &self.render {
+            app.insert_resource(render.clone());
+        }
+
+        app.insert_resource(self.backend.clone())
+            .init_non_send_resource::<AudioOutput>()
+            .init_resource::<AudioGraph>()
+            .init_resource::<WaveErrors>()
+            .add_event::<NoteEvent>()
+            .add_systems(PostUpdate, build_audio_chains)
+            .add_systems(
+                Update,
+                (
+                    update_audio,
+                    update_samples,
+                    update_oscillators,
+                    play_notes.after(update_oscillators),
+                    update_generators,
+                    update_samplers,
+                    update_wavetables,
+                    update_filters,
+                    update_envelopes,
+                    update_delays,
+                    update_reverbs,
+                    update_mixers,
+                    update_pans,
+                    collect_wave_errors,
+                    report_realtime_violations,
+                ),
+            )
+            .add_systems(
+                Update,
+                (insert_render_patch, render_offline).run_if(resource_exists::<RenderSettings>()),
+            )
+            .add_systems(
+                Update,
+                tick_audio_clock.run_if(not(resource_exists::<RenderSettings>())),
+            )
+            .add_systems(Last, finalize_audio_sink);
     }
 }
 
 pub struct AudioOutput {
     // pub(crate) knyst: KnystCommands,
-    _backend: CpalBackend,
+    backend: Backend,
     _error_receiver: std::sync::mpsc::Receiver<String>,
+    // streams are wrapped in `Realtime` to report heap use per node.
+    realtime_debug: bool,
 }
 
-impl Default for AudioOutput {
-    fn default() -> Self {
-        let (error_sender, _error_receiver) = std::sync::mpsc::channel();
+impl FromWorld for AudioOutput {
+    fn from_world(world: &mut World) -> Self {
+        let settings = world
+            .get_resource::<BackendSettings>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(render) = world.get_resource::<RenderSettings>() {
+            let sink = WavSink::new(
+                &render.path,
+                render.bit_depth,
+                OFFLINE_SAMPLE_RATE,
+                Some(render.seconds),
+            )
+            .map_err(|err| error!("Cannot create render file {:?}. Error: {err}", render.path))
+            .ok();
+
+            // nothing can be rendered without the file.
+            if sink.is_none() {
+                world.send_event(AppExit);
+            }
 
-        let mut backend = CpalBackend::new(CpalBackendOptions::default())
-            .unwrap_or_else(|err| panic!("Cannot initialize cpal backend. Error: {err}"));
+            return Self::start(
+                Backend::Offline(OfflineBackend::new(OFFLINE_SAMPLE_RATE, AUDIO_SIZE, sink)),
+                settings.realtime_debug,
+            );
+        }
 
-        let _sphere = KnystSphere::start(
-            &mut backend,
-            SphereSettings {
-                num_inputs: 0,
-                num_outputs: 2,
-                ..Default::default()
+        let dummy = || Backend::Offline(OfflineBackend::new(OFFLINE_SAMPLE_RATE, AUDIO_SIZE, None));
+
+        let backend = match settings.kind {
+            AudioBackendKind::Cpal => match CpalBackend::new(CpalBackendOptions) {
+                Ok(backend) => Backend::Cpal(backend),
+                Err(err) => {
+                    warn!("Cannot initialize cpal backend, falling back to dummy. Error: {err}");
+                    dummy()
+                }
             },
-            Box::new(move |error| {
-                error_sender.send(format!("{error}")).unwrap();
-            }),
-        );
+            AudioBackendKind::Dummy => dummy(),
+            AudioBackendKind::File => match WavSink::new(
+                &settings.output,
+                BitDepth::default(),
+                OFFLINE_SAMPLE_RATE,
+                None,
+            ) {
+                Ok(sink) => Backend::Offline(OfflineBackend::new(
+                    OFFLINE_SAMPLE_RATE,
+                    AUDIO_SIZE,
+                    Some(sink),
+                )),
+                Err(err) => {
+                    warn!(
+                        "Cannot create audio file {:?}, falling back to dummy. Error: {err}",
+                        settings.output
+                    );
+                    dummy()
+                }
+            },
+        };
+
+        info!("audio backend: {:?}", settings.kind);
+
+        Self::start(backend, settings.realtime_debug)
+    }
+}
+
+impl AudioOutput {
+    fn start(mut backend: Backend, realtime_debug: bool) -> Self {
+        let (error_sender, _error_receiver) = std::sync::mpsc::channel();
+
+        let settings = SphereSettings {
+            num_inputs: 0,
+            num_outputs: 2,
+            ..Default::default()
+        };
+
+        let error_handler = Box::new(move |error: KnystError| {
+            error_sender.send(format!("{error}")).unwrap();
+        });
+
+        let sphere = match &mut backend {
+            Backend::Cpal(backend) => KnystSphere::start(backend, settings, error_handler),
+            Backend::Offline(backend) => KnystSphere::start(backend, settings, error_handler),
+        };
+
+        if let Err(e) = sphere {
+            error!("failed to start knyst sphere - {:?}", e);
+        }
+
+        if realtime_debug {
+            info!("realtime debug: reporting heap use on the audio thread");
+        }
 
         Self {
             _error_receiver,
-            _backend: backend,
+            backend,
+            realtime_debug,
         }
     }
-}
 
-impl AudioOutput {
-    fn play_stream(&mut self, stream: Box<Vec<Box<AudioSend>>>) -> Vec<NodeId> {
-        let mut chain_out: Vec<NodeId> = vec![];
+    pub fn get_offline_mut(&mut self) -> Option<&mut OfflineBackend> {
+        match &mut self.backend {
+            Backend::Offline(backend) => Some(backend),
+            _ => None,
+        }
+    }
+
+    /// Pushes every stream of a chain onto the knyst graph, each one reading from the one before.
+    /// `AudioSend::Output` connects the previous node to the graph output,
+    /// `AudioSend::Bus` to an input of a node built by another chain.
+    /// Every link returns its node and the stats of its stream, links that only connect nodes get empty ones.
+    /// Bus sends and outputs without a node to read from are skipped.
+    pub fn play_stream(&mut self, stream: Vec<AudioSend>) -> Vec<Option<(NodeId, StreamStats)>> {
+        let mut chain_out: Option<NodeId> = None;
+        let mut links = vec![];
 
         for stream in stream.into_iter() {
-            match *stream {
-                AudioSend::Read(stream) => chain_out.push(self.push(stream, chain_out.last())),
-                AudioSend::Oscillator(stream) => {
-                    chain_out.push(self.push(stream, chain_out.last()))
-                }
-                AudioSend::Output => todo!(),
+            let link = match stream {
+                AudioSend::Read(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Oscillator(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Generator(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Sampler(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Wavetable(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Filter(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Envelope(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Delay(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Reverb(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Pan(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Mixer(stream) => Some(self.push(stream, chain_out.as_ref())),
+                AudioSend::Bus(bus, input) => match chain_out {
+                    Some(node_address) => {
+                        knyst_commands()
+                            .connect(node_address.to(bus).to_index(input * 2).channels(2));
+                        Some((node_address, StreamStats::default()))
+                    }
+                    None => {
+                        warn!("skipping bus send, it needs a node to read from.");
+                        None
+                    }
+                },
+                AudioSend::Output => match chain_out {
+                    Some(node_address) => {
+                        knyst_commands().connect(node_address.to_graph_out().channels(2));
+                        Some((node_address, StreamStats::default()))
+                    }
+                    None => {
+                        warn!("skipping audio output, it needs a node to read from.");
+                        None
+                    }
+                },
+            };
+
+            if let Some((node_address, _)) = &link {
+                chain_out = Some(*node_address);
             }
+            links.push(link);
         }
 
-        knyst_commands().connect(chain_out.last().unwrap().to_graph_out().channels(2));
-        chain_out
+        links
     }
 
-    fn push(&mut self, stream: impl Gen + Send + 'static, inputs: Option<&NodeId>) -> NodeId {
+    fn push(
+        &mut self,
+        stream: impl Gen + Send + 'static,
+        inputs: Option<&NodeId>,
+    ) -> (NodeId, StreamStats) {
         let inputs = match inputs {
             None => inputs!(),
             Some(node_address) => {
@@ -95,28 +300,47 @@ impl AudioOutput {
             }
         };
 
-        let id = knyst_commands().push(stream, inputs);
+        let (stream, load) = Profiled::new(stream);
 
-        id
-    }
-}
+        if self.realtime_debug {
+            let (stream, report) = Realtime::new(stream);
+            let id = knyst_commands().push(stream, inputs);
 
-pub struct Audio {
-    chain: Chain,
-}
+            return (
+                id,
+                StreamStats {
+                    load,
+                    realtime: Some(report),
+                },
+            );
+        }
 
-impl Component for Audio {
-    type Storage = TableStorage;
-}
+        let id = knyst_commands().push(stream, inputs);
 
-impl Audio {
-    pub fn new(chain: Chain) -> Self {
-        Self { chain }
+        (
+            id,
+            StreamStats {
+                load,
+                realtime: None,
+            },
+        )
     }
 }
 
 #[derive(Component)]
-pub struct AudioId(pub NodeId);
+pub struct AudioId(#[allow(dead_code)] pub NodeId);
+
+/// Processing load of the stream a node built, see `DspLoad`.
+#[derive(Component, Deref)]
+pub struct AudioLoad(pub Arc<DspLoad>);
+
+/// Handles the audio thread writes for a pushed stream.
+#[derive(Default)]
+pub struct StreamStats {
+    pub load: Arc<DspLoad>,
+    /// only with realtime debug.
+    pub realtime: Option<Arc<RealtimeReport>>,
+}
 
 #[derive(Deref, DerefMut)]
 pub struct AudioControl<T: Streamable>(T::Control);
@@ -132,105 +356,164 @@ pub trait Streamable: Send + Sync + 'static {
     fn to_stream(
         &mut self,
         // k: &mut KnystCommands,
-        lua: &Res<Assets<LuaAsset>>,
+        assets: &DspAssets,
     ) -> Option<AudioSendControl>;
 }
 
-fn play_audio(
-    mut commands: Commands,
-    mut audio_query: Query<(Entity, &mut Audio), Without<AudioId>>,
-    lua_assets: Res<Assets<LuaAsset>>,
-    mut audio_graph: NonSendMut<AudioOutput>,
-) {
-    for (entity, mut audio) in audio_query.iter_mut() {
-        let (stream, control) = audio
-            .chain
-            .items
-            .iter_mut()
-            .map(|i| match i {
-                Dsp::Input(i) => i.to_stream(&lua_assets),
-                Dsp::Read(i) => i.to_stream(&lua_assets),
-                Dsp::Output => Some(AudioSendControl::Output),
-            })
-            .map(|i| i.unwrap()) // TODO: handle None by failing entire chain
-            .fold((vec![], vec![]), |mut res, i| {
-                match i {
-                    AudioSendControl::Read((stream, control)) => {
-                        res.0.push(Box::new(AudioSend::Read(stream)));
-                        res.1.push(AC::Read(control));
-                    }
-
-                    AudioSendControl::Oscillator((stream, control)) => {
-                        res.0.push(Box::new(AudioSend::Oscillator(stream)));
-                        res.1.push(AC::Oscillator(control));
-                    }
-
-                    AudioSendControl::Output => {
-                        res.0.push(Box::new(AudioSend::Output));
-                        res.1.push(AC::Output);
-                    }
-                }
+/// Assets a stream can be built from, streams return `None` until theirs are loaded.
+#[derive(SystemParam)]
+pub struct DspAssets<'w> {
+    pub lua: Res<'w, Assets<LuaAsset>>,
+    pub samples: Res<'w, Assets<SampleAsset>>,
+}
 
-                res
-            });
+/// Inserts the control handle of a built stream onto the entity that owns it.
+/// The load of the stream goes next to it, bus and output links have no stream of their own.
+pub fn insert_control(
+    commands: &mut Commands,
+    entity: Entity,
+    control: AC,
+    node_address: NodeId,
+    load: Arc<DspLoad>,
+) {
+    if !matches!(control, AC::Bus | AC::Output) {
+        commands.entity(entity).insert(AudioLoad(load));
+    }
 
-        if (stream.len() == 0) || (control.len() == 0) {
-            continue;
+    match control {
+        AC::Read(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Read>(control)));
+        }
+        AC::Oscillator(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Oscillator>(control)));
+        }
+        AC::Generator(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Generator>(control)));
+        }
+        AC::Sampler(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Sampler>(control)));
+        }
+        AC::Wavetable(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Wavetable>(control)));
+        }
+        AC::Filter(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Filter>(control)));
+        }
+        AC::Envelope(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Envelope>(control)));
+        }
+        AC::Delay(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Delay>(control)));
+        }
+        AC::Reverb(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Reverb>(control)));
+        }
+        AC::Pan(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Pan>(control)));
+        }
+        AC::Mixer(control) => {
+            commands
+                .entity(entity)
+                .insert((AudioId(node_address), AudioControl::<Mixer>(control)));
+        }
+        // the entity is the mixer, its node belongs to the mixer chain.
+        AC::Bus => (),
+        AC::Output => {
+            commands.entity(entity).insert(AudioId(node_address));
         }
-
-        let node_addresses = audio_graph.play_stream(Box::new(stream));
-
-        control
-            .into_iter()
-            .zip(node_addresses.into_iter())
-            .for_each(|(control, node_address)| match control {
-                AC::Read(control) => {
-                    commands
-                        .entity(entity)
-                        .insert((AudioId(node_address), AudioControl::<Read>(control)));
-                }
-                AC::Oscillator(control) => {
-                    commands
-                        .entity(entity)
-                        .insert((AudioId(node_address), AudioControl::<Oscillator>(control)));
-                }
-                AC::Output => todo!(),
-            });
     }
 }
 
+/// Rebuilds the chains using an edited lua script.
+/// Oscillators that are already playing fade into the edited wave instead, see `OscillatorControl::hot_swap`.
 fn update_audio(
-    mut commands: Commands,
-    audio_query: Query<(Entity, &Audio, &AudioId), With<AudioControl<Oscillator>>>,
+    mut graph: ResMut<AudioGraph>,
     mut lua_asset_event: EventReader<AssetEvent<LuaAsset>>,
-    // audio_graph: NonSendMut<AudioOutput>,
+    lua_assets: Res<Assets<LuaAsset>>,
+    oscillators: Query<&AudioControl<Oscillator>>,
+    config: Res<ConfigAsset>,
 ) {
     for ev in lua_asset_event.read() {
         match ev {
             AssetEvent::LoadedWithDependencies { id: asset_id } => {
-                for (entity, audio, audio_id) in audio_query.iter() {
-                    audio
-                        .chain
-                        .items
-                        .iter()
-                        .for_each(|send_control| match send_control {
-                            Dsp::Input(audio) => {
-                                if audio
-                                    .lua_handle
-                                    .iter()
-                                    .any(|handle| handle.id() == asset_id.to_owned())
-                                {
-                                    knyst_commands().free_node(audio_id.0.clone());
-                                    commands.entity(entity).remove::<AudioId>();
-                                    commands.entity(entity).remove::<AudioControl<Read>>();
-                                    commands.entity(entity).remove::<AudioControl<Oscillator>>();
+                let mut dirty = vec![];
+
+                for (i, chain) in graph.get_chain().iter().enumerate() {
+                    let built = graph.get_nodes(i).is_some();
+
+                    chain.flatten().iter().for_each(|(dsp, entity)| match dsp {
+                        Dsp::Input(audio)
+                            if audio
+                                .lua_handle
+                                .iter()
+                                .any(|handle| handle.id() == *asset_id) =>
+                        {
+                            let control = entity.and_then(|entity| oscillators.get(entity).ok());
+
+                            match (built, control, audio.scripts(&lua_assets)) {
+                                (true, Some(control), Some(scripts)) => {
+                                    info!("fading into edited wave of chain: {}", i);
+                                    control.hot_swap(scripts, config.wave_fade);
                                 }
+                                _ => dirty.push(i),
                             }
-                            _ => (),
-                        });
+                        }
+                        Dsp::Wavetable(wavetable) if wavetable.source.uses_lua(*asset_id) => {
+                            dirty.push(i)
+                        }
+                        _ => (),
+                    });
                 }
+
+                dirty.into_iter().for_each(|i| graph.mark_dirty(i));
             }
             _ => return,
         }
     }
 }
+
+/// Rebuilds the chains playing a sample or wavetable when the file changes on disk.
+fn update_samples(
+    mut graph: ResMut<AudioGraph>,
+    mut sample_asset_event: EventReader<AssetEvent<SampleAsset>>,
+) {
+    for ev in sample_asset_event.read() {
+        if let AssetEvent::Modified { id: asset_id } = ev {
+            let dirty = graph
+                .get_chain()
+                .iter()
+                .enumerate()
+                .filter(|(_, chain)| {
+                    chain.flatten().iter().any(|(dsp, _)| match dsp {
+                        Dsp::Sampler(sampler) => sampler.handle.id() == *asset_id,
+                        Dsp::Wavetable(wavetable) => wavetable.source.uses_sample(*asset_id),
+                        _ => false,
+                    })
+                })
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>();
+
+            dirty.into_iter().for_each(|i| graph.mark_dirty(i));
+        }
+    }
+}
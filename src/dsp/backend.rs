@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use bevy::{
+    app::AppExit,
+    ecs::{
+        event::EventReader,
+        system::{NonSendMut, Res, Resource},
+    },
+    log::{error, info, warn},
+    time::Time,
+};
+use knyst::{
+    audio_backend::{AudioBackend, AudioBackendError, CpalBackend},
+    controller::Controller,
+    graph::{Graph, RunGraph, RunGraphSettings},
+    KnystError, Resources,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::components::config::ConfigAsset;
+
+use super::{audio_graph::AudioOutput, render::WavSink};
+
+pub const OFFLINE_SAMPLE_RATE: usize = 48000;
+/// the manual clock never catches up more than this, so a long frame does not stall the app.
+pub const MAX_CLOCK_LAG_SECONDS: f64 = 0.5;
+
+/// Audio driver knyst runs on.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackendKind {
+    /// sound card output.
+    #[default]
+    Cpal,
+    /// no output, blocks are processed from the app clock.
+    Dummy,
+    /// blocks are processed from the app clock and written into a wav file.
+    File,
+}
+
+impl AudioBackendKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cpal" => Some(AudioBackendKind::Cpal),
+            "dummy" => Some(AudioBackendKind::Dummy),
+            "file" => Some(AudioBackendKind::File),
+            _ => None,
+        }
+    }
+}
+
+/// Backend selection, from the config file and overridden from the command line.
+/// `pulsar --backend file --output out.wav --realtime-debug`
+#[derive(Resource, Clone, Debug, Default)]
+pub struct BackendSettings {
+    pub kind: AudioBackendKind,
+    pub output: PathBuf,
+    /// wraps every stream to report heap use on the audio thread per node.
+    pub realtime_debug: bool,
+}
+
+impl BackendSettings {
+    pub fn new(config: &ConfigAsset, args: impl Iterator<Item = String>) -> Self {
+        let mut kind = config.audio_backend;
+        let mut output = PathBuf::from(&config.audio_file);
+        let mut realtime_debug = config.realtime_debug;
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--backend" => {
+                    kind = args
+                        .next()
+                        .and_then(|s| AudioBackendKind::from_name(&s))
+                        .unwrap_or(kind)
+                }
+                "--output" => output = args.next().map(PathBuf::from).unwrap_or(output),
+                "--realtime-debug" => realtime_debug = true,
+                _ => (),
+            }
+        }
+
+        // heap calls are counted by the allocator of the feature.
+        if realtime_debug && !cfg!(feature = "realtime-debug") {
+            warn!("realtime debug needs a build with `--features realtime-debug`");
+            realtime_debug = false;
+        }
+
+        Self {
+            kind,
+            output,
+            realtime_debug,
+        }
+    }
+}
+
+pub enum Backend {
+    Cpal(CpalBackend),
+    Offline(OfflineBackend),
+}
+
+/// OfflineBackend - a knyst backend that is not driven by a sound card.
+/// Blocks are only processed when `process_block` is called, either by hand or from the clock.
+pub struct OfflineBackend {
+    sample_rate: usize,
+    block_size: usize,
+    run_graph: Option<RunGraph>,
+    sink: Option<WavSink>,
+
+    // manual clock, in samples not yet processed.
+    pending: f64,
+}
+
+impl OfflineBackend {
+    pub fn new(sample_rate: usize, block_size: usize, sink: Option<WavSink>) -> Self {
+        Self {
+            sample_rate,
+            block_size,
+            run_graph: None,
+            sink,
+            pending: 0.0,
+        }
+    }
+
+    /// processes a single block of the graph, writing the output into the sink.
+    pub fn process_block(&mut self) -> Result<(), hound::Error> {
+        let run_graph = match self.run_graph.as_mut() {
+            Some(run_graph) => run_graph,
+            None => return Ok(()),
+        };
+
+        run_graph.process_block();
+
+        if let Some(sink) = self.sink.as_mut() {
+            let out = run_graph.graph_output_buffers();
+
+            for i in 0..self.block_size {
+                sink.write(out.read(0, i), out.read(1, i))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// moves the manual clock forward, processing every block that fits in the elapsed time.
+    pub fn advance(&mut self, seconds: f32) -> Result<(), hound::Error> {
+        self.pending = (self.pending + seconds as f64 * self.sample_rate as f64)
+            .min(MAX_CLOCK_LAG_SECONDS * self.sample_rate as f64);
+
+        while self.pending >= self.block_size as f64 {
+            self.pending -= self.block_size as f64;
+            self.process_block()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_sink_mut(&mut self) -> Option<&mut WavSink> {
+        self.sink.as_mut()
+    }
+}
+
+impl AudioBackend for OfflineBackend {
+    fn start_processing_return_controller(
+        &mut self,
+        mut graph: Graph,
+        resources: Resources,
+        run_graph_settings: RunGraphSettings,
+        error_handler: Box<dyn FnMut(KnystError) + Send + 'static>,
+    ) -> Result<Controller, AudioBackendError> {
+        let (run_graph, resources_command_sender, resources_command_receiver) =
+            RunGraph::new(&mut graph, resources, run_graph_settings)?;
+
+        let controller = Controller::new(
+            graph,
+            error_handler,
+            resources_command_sender,
+            resources_command_receiver,
+        );
+
+        self.run_graph = Some(run_graph);
+
+        Ok(controller)
+    }
+
+    fn stop(&mut self) -> Result<(), AudioBackendError> {
+        self.run_graph = None;
+        Ok(())
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn block_size(&self) -> Option<usize> {
+        Some(self.block_size)
+    }
+
+    fn native_output_channels(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn native_input_channels(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// Drives the dummy and file backends from the app clock.
+pub fn tick_audio_clock(time: Res<Time>, mut audio_output: NonSendMut<AudioOutput>) {
+    if let Some(backend) = audio_output.get_offline_mut() {
+        if let Err(e) = backend.advance(time.delta_seconds()) {
+            error!("failed to write audio file - {}", e);
+        }
+    }
+}
+
+/// Makes sure the file sink has a valid header when the app closes.
+pub fn finalize_audio_sink(
+    mut exit_event: EventReader<AppExit>,
+    mut audio_output: NonSendMut<AudioOutput>,
+) {
+    if exit_event.read().next().is_none() {
+        return;
+    }
+
+    if let Some(sink) = audio_output
+        .get_offline_mut()
+        .and_then(|backend| backend.get_sink_mut())
+    {
+        match sink.finalize() {
+            Ok(_) => info!("audio file finalized."),
+            Err(e) => error!("failed to finalize audio file - {}", e),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_TICKS: f32 = 6.0;
+pub const DEFAULT_FEEDBACK: f32 = 0.4;
+pub const DEFAULT_MIX: f32 = 0.35;
+
+pub const MAX_DELAY_SECONDS: f32 = 4.0;
+// buffers are sized up front so nothing is allocated on the audio thread.
+pub const MAX_SAMPLE_RATE: f32 = 96000.0;
+
+// one pole smoothing of the delay time, avoids zipper noise on tempo changes.
+const TIME_SMOOTHING: f32 = 0.001;
+
+#[derive(Clone)]
+pub struct Delay;
+
+pub struct DelayStream {
+    time: Arc<AtomicF32>,
+    feedback: Arc<AtomicF32>,
+    mix: Arc<AtomicF32>,
+
+    buffer: [Vec<f32>; 2],
+    write_idx: usize,
+
+    // delay in samples, follows `time`. 0.0 until the first block.
+    current: f32,
+}
+
+impl DelayStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let len = self.buffer[0].len();
+
+        let target = (self.time.load(Ordering::Relaxed) * sample_rate).clamp(1.0, (len - 2) as f32);
+        let feedback = self.feedback.load(Ordering::Relaxed).clamp(0.0, 0.98);
+        let mix = self.mix.load(Ordering::Relaxed).clamp(0.0, 1.0);
+
+        // jump straight to the first time that is set.
+        if self.current == 0.0 {
+            self.current = target;
+        }
+
+        (0..ctx.block_size()).for_each(|i| {
+            self.current += (target - self.current) * TIME_SMOOTHING;
+
+            let read = self.write_idx as f32 - self.current + len as f32;
+            let idx = read.floor() as usize;
+            let frac = read.fract();
+
+            (0..2).for_each(|ch| {
+                let buf = &mut self.buffer[ch];
+
+                let a = buf[idx % len];
+                let b = buf[(idx + 1) % len];
+                let delayed = a + (b - a) * frac;
+
+                let dry = ctx.inputs.read(ch, i);
+                buf[self.write_idx] = dry + delayed * feedback;
+
+                ctx.outputs.write(dry * (1.0 - mix) + delayed * mix, ch, i);
+            });
+
+            self.write_idx = (self.write_idx + 1) % len;
+        });
+    }
+}
+
+impl Gen for DelayStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct DelayControl {
+    time: Arc<AtomicF32>,
+    feedback: Arc<AtomicF32>,
+    mix: Arc<AtomicF32>,
+}
+
+impl DelayControl {
+    /// delay time in seconds, up to `MAX_DELAY_SECONDS`.
+    pub fn set_time(&self, seconds: f32) {
+        self.time.store(seconds, Ordering::Relaxed);
+    }
+
+    pub fn set_feedback(&self, feedback: f32) {
+        self.feedback.store(feedback, Ordering::Relaxed);
+    }
+
+    /// 0.0 is dry, 1.0 only the echo.
+    pub fn set_mix(&self, mix: f32) {
+        self.mix.store(mix, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Delay {
+    type Stream = DelayStream;
+    type Control = DelayControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let time = Arc::new(AtomicF32::new(0.0));
+        let feedback = Arc::new(AtomicF32::new(DEFAULT_FEEDBACK));
+        let mix = Arc::new(AtomicF32::new(DEFAULT_MIX));
+
+        let control = DelayControl {
+            time: time.clone(),
+            feedback: feedback.clone(),
+            mix: mix.clone(),
+        };
+
+        let len = (MAX_DELAY_SECONDS * MAX_SAMPLE_RATE) as usize;
+
+        let stream = DelayStream {
+            time,
+            feedback,
+            mix,
+
+            buffer: [vec![0.0; len], vec![0.0; len]],
+            write_idx: 0,
+
+            current: 0.0,
+        };
+
+        Some(AudioSendControl::Delay((stream, control)))
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_ATTACK: f32 = 0.01;
+pub const DEFAULT_DECAY: f32 = 0.1;
+pub const DEFAULT_SUSTAIN: f32 = 0.7;
+pub const DEFAULT_RELEASE: f32 = 0.3;
+
+// shortest segment, avoids clicks and division by zero.
+const MIN_TIME: f32 = 0.001;
+
+#[derive(Clone)]
+pub struct Envelope;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+pub struct EnvelopeStream {
+    attack: Arc<AtomicF32>,
+    decay: Arc<AtomicF32>,
+    sustain: Arc<AtomicF32>,
+    release: Arc<AtomicF32>,
+
+    triggers: Arc<AtomicUsize>,
+    last_trigger: usize,
+
+    stage: Stage,
+    level: f32,
+    release_step: f32,
+}
+
+impl EnvelopeStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let triggers = self.triggers.load(Ordering::Relaxed);
+
+        // a bang restarts the attack from the current level.
+        if triggers != self.last_trigger {
+            self.stage = Stage::Attack;
+        }
+
+        self.last_trigger = triggers;
+
+        let attack_step = 1.0 / (self.attack.load(Ordering::Relaxed).max(MIN_TIME) * sample_rate);
+        let sustain = self.sustain.load(Ordering::Relaxed).clamp(0.0, 1.0);
+        let decay_step =
+            (1.0 - sustain) / (self.decay.load(Ordering::Relaxed).max(MIN_TIME) * sample_rate);
+
+        (0..ctx.block_size()).for_each(|i| {
+            match self.stage {
+                Stage::Idle => self.level = 0.0,
+                Stage::Attack => {
+                    self.level += attack_step;
+
+                    if self.level >= 1.0 {
+                        self.level = 1.0;
+                        self.stage = Stage::Decay;
+                    }
+                }
+                Stage::Decay => {
+                    self.level -= decay_step;
+
+                    // a bang plays the whole envelope, release starts at the sustain level.
+                    if self.level <= sustain {
+                        self.level = sustain;
+                        self.start_release(sample_rate);
+                    }
+                }
+                Stage::Sustain => self.level = sustain,
+                Stage::Release => {
+                    self.level -= self.release_step;
+
+                    if self.level <= 0.0 {
+                        self.level = 0.0;
+                        self.stage = Stage::Idle;
+                    }
+                }
+            }
+
+            ctx.outputs.write(ctx.inputs.read(0, i) * self.level, 0, i);
+            ctx.outputs.write(ctx.inputs.read(1, i) * self.level, 1, i);
+        });
+    }
+
+    fn start_release(&mut self, sample_rate: f32) {
+        let release = self.release.load(Ordering::Relaxed).max(MIN_TIME);
+
+        self.release_step = self.level / (release * sample_rate);
+        self.stage = Stage::Release;
+    }
+}
+
+impl Gen for EnvelopeStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct EnvelopeControl {
+    attack: Arc<AtomicF32>,
+    decay: Arc<AtomicF32>,
+    sustain: Arc<AtomicF32>,
+    release: Arc<AtomicF32>,
+
+    triggers: Arc<AtomicUsize>,
+}
+
+impl EnvelopeControl {
+    /// restarts the envelope, it runs through to the end.
+    pub fn trigger(&self) {
+        self.triggers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// times in seconds, sustain is a level between 0.0 and 1.0.
+    pub fn set_adsr(&self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.attack.store(attack, Ordering::Relaxed);
+        self.decay.store(decay, Ordering::Relaxed);
+        self.sustain.store(sustain, Ordering::Relaxed);
+        self.release.store(release, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Envelope {
+    type Stream = EnvelopeStream;
+    type Control = EnvelopeControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let attack = Arc::new(AtomicF32::new(DEFAULT_ATTACK));
+        let decay = Arc::new(AtomicF32::new(DEFAULT_DECAY));
+        let sustain = Arc::new(AtomicF32::new(DEFAULT_SUSTAIN));
+        let release = Arc::new(AtomicF32::new(DEFAULT_RELEASE));
+
+        let triggers = Arc::new(AtomicUsize::new(0));
+
+        let control = EnvelopeControl {
+            attack: attack.clone(),
+            decay: decay.clone(),
+            sustain: sustain.clone(),
+            release: release.clone(),
+
+            triggers: triggers.clone(),
+        };
+
+        let stream = EnvelopeStream {
+            attack,
+            decay,
+            sustain,
+            release,
+
+            triggers,
+            last_trigger: 0,
+
+            stage: Stage::Idle,
+            level: 0.0,
+            release_step: 0.0,
+        };
+
+        Some(AudioSendControl::Envelope((stream, control)))
+    }
+}
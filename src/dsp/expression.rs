@@ -0,0 +1,405 @@
+use std::f64::consts::TAU;
+
+use rlua::{Context, Error, FromLua, Function, Lua, Result, Value};
+
+use crate::lua::load_fn;
+
+pub const TRACE_STRING: &str = include_str!("../../assets/lua/common/instrument/trace.lua");
+const TRACE: &str = "TRACE_FN";
+
+/// Expression tree recorded from a traced `FN`.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Const(f64),
+    Time,
+    Frequency,
+    Osc {
+        wave: UnaryOp,
+        frequency: Box<Expr>,
+        slot: usize,
+    },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum UnaryOp {
+    Neg,
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Exp,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl UnaryOp {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "neg" => Some(UnaryOp::Neg),
+            "sin" => Some(UnaryOp::Sin),
+            "cos" => Some(UnaryOp::Cos),
+            "abs" => Some(UnaryOp::Abs),
+            "sqrt" => Some(UnaryOp::Sqrt),
+            "exp" => Some(UnaryOp::Exp),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            UnaryOp::Neg => -x,
+            UnaryOp::Sin => x.sin(),
+            UnaryOp::Cos => x.cos(),
+            UnaryOp::Abs => x.abs(),
+            UnaryOp::Sqrt => x.sqrt(),
+            UnaryOp::Exp => x.exp(),
+        }
+    }
+}
+
+impl BinaryOp {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "add" => Some(BinaryOp::Add),
+            "sub" => Some(BinaryOp::Sub),
+            "mul" => Some(BinaryOp::Mul),
+            "div" => Some(BinaryOp::Div),
+            "mod" => Some(BinaryOp::Mod),
+            "pow" => Some(BinaryOp::Pow),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            // lua uses floored modulo
+            BinaryOp::Mod => a - (a / b).floor() * b,
+            BinaryOp::Pow => a.powf(b),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for Expr {
+    fn from_lua(value: Value<'lua>, _ctx: Context<'lua>) -> Result<Self> {
+        let table = match value {
+            Value::Number(n) => return Ok(Expr::Const(n)),
+            Value::Integer(i) => return Ok(Expr::Const(i as f64)),
+            Value::Table(table) => table,
+            _ => return Err(expr_error("value is not a number or an expression")),
+        };
+
+        let op = table.get::<_, String>("op")?;
+
+        let expr = match op.as_str() {
+            "time" => Expr::Time,
+            "frequency" => Expr::Frequency,
+            "osc" => {
+                let wave = table.get::<_, String>("a")?;
+
+                Expr::Osc {
+                    wave: UnaryOp::from_name(&wave)
+                        .ok_or_else(|| expr_error("unknown wave function"))?,
+                    frequency: Box::new(table.get::<_, Expr>("b")?),
+                    slot: table.get::<_, usize>("slot")?,
+                }
+            }
+            op => match (UnaryOp::from_name(op), BinaryOp::from_name(op)) {
+                (Some(unary), _) => Expr::Unary(unary, Box::new(table.get::<_, Expr>("a")?)),
+                (_, Some(binary)) => Expr::Binary(
+                    binary,
+                    Box::new(table.get::<_, Expr>("a")?),
+                    Box::new(table.get::<_, Expr>("b")?),
+                ),
+                _ => return Err(expr_error("unknown operation")),
+            },
+        };
+
+        Ok(expr)
+    }
+}
+
+fn expr_error(message: &str) -> Error {
+    Error::FromLuaConversionError {
+        from: "Value",
+        to: "Expr",
+        message: Some(message.to_string()),
+    }
+}
+
+/// Flattened expression, evaluated with a stack.
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Const(f64),
+    Time,
+    Frequency,
+    Osc(UnaryOp, usize),
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+}
+
+/// Native version of a wave script, evaluates both channels per sample.
+/// Follows `wave.lua` exactly and in double precision like lua, see `next`.
+#[derive(Clone)]
+pub struct WaveProgram {
+    left: Vec<Op>,
+    right: Vec<Op>,
+
+    // oscillators, indexed by the `W` call order.
+    slots: usize,
+    // phase of every oscillator, `P[W_I]` in lua.
+    phases: Vec<f64>,
+    increments: Vec<f64>,
+
+    stack: Vec<f64>,
+}
+
+impl WaveProgram {
+    pub fn new(left: Expr, right: Expr) -> Self {
+        let mut program = WaveProgram {
+            left: vec![],
+            right: vec![],
+            slots: 0,
+            phases: vec![],
+            increments: vec![],
+            stack: vec![],
+        };
+
+        let mut left_ops = vec![];
+        let mut right_ops = vec![];
+        program.flatten(&left, &mut left_ops);
+        program.flatten(&right, &mut right_ops);
+
+        program.left = left_ops;
+        program.right = right_ops;
+        program.phases = vec![0.0; program.slots];
+        program.increments = vec![0.0; program.slots];
+        program.stack = Vec::with_capacity(program.left.len().max(program.right.len()));
+
+        program
+    }
+
+    pub fn len(&self) -> usize {
+        self.left.len() + self.right.len()
+    }
+
+    fn flatten(&mut self, expr: &Expr, ops: &mut Vec<Op>) {
+        match expr {
+            Expr::Const(c) => ops.push(Op::Const(*c)),
+            Expr::Time => ops.push(Op::Time),
+            Expr::Frequency => ops.push(Op::Frequency),
+            Expr::Osc {
+                wave,
+                frequency,
+                slot,
+            } => {
+                self.slots = self.slots.max(slot + 1);
+
+                self.flatten(frequency, ops);
+                ops.push(Op::Osc(*wave, *slot));
+            }
+            Expr::Unary(op, a) => {
+                self.flatten(a, ops);
+                ops.push(Op::Unary(*op));
+            }
+            Expr::Binary(op, a, b) => {
+                self.flatten(a, ops);
+                self.flatten(b, ops);
+                ops.push(Op::Binary(*op));
+            }
+        }
+    }
+
+    /// t - seconds since start, frequency - in radians, interval - seconds per sample
+    /// Like `W` in `wave.lua`, an oscillator plays `wave(phase)` and its phase advances by `interval * f` every sample.
+    pub fn next(&mut self, t: f64, frequency: f32, interval: f64) -> (f32, f32) {
+        let frequency = frequency as f64;
+
+        let left = Self::eval(
+            &self.left,
+            &mut self.stack,
+            &self.phases,
+            &mut self.increments,
+            t,
+            frequency,
+            interval,
+        );
+        let right = Self::eval(
+            &self.right,
+            &mut self.stack,
+            &self.phases,
+            &mut self.increments,
+            t,
+            frequency,
+            interval,
+        );
+
+        // a shared oscillator only advances once per sample, floored like lua's `%`.
+        self.phases
+            .iter_mut()
+            .zip(&self.increments)
+            .for_each(|(phase, inc)| *phase = (*phase + inc).rem_euclid(TAU));
+
+        (left as f32, right as f32)
+    }
+
+    fn eval(
+        ops: &[Op],
+        stack: &mut Vec<f64>,
+        phases: &[f64],
+        increments: &mut [f64],
+        t: f64,
+        frequency: f64,
+        interval: f64,
+    ) -> f64 {
+        stack.clear();
+
+        ops.iter().for_each(|op| {
+            let v = match *op {
+                Op::Const(c) => c,
+                Op::Time => t,
+                Op::Frequency => frequency,
+                Op::Osc(wave, slot) => {
+                    let f = stack.pop().unwrap_or_default();
+                    increments[slot] = interval * f;
+
+                    wave.apply(phases[slot])
+                }
+                Op::Unary(op) => {
+                    let a = stack.pop().unwrap_or_default();
+                    op.apply(a)
+                }
+                Op::Binary(op) => {
+                    let b = stack.pop().unwrap_or_default();
+                    let a = stack.pop().unwrap_or_default();
+                    op.apply(a, b)
+                }
+            };
+
+            stack.push(v);
+        });
+
+        stack.pop().unwrap_or_default()
+    }
+}
+
+/// Traces `FN` of an already loaded wave script.
+/// Returns None when the script has to be interpreted.
+pub fn compile(lua: &Lua) -> Option<WaveProgram> {
+    load_fn(lua, "lua_pulse", TRACE_STRING);
+
+    lua.context(|ctx| {
+        let function: Function = ctx.globals().get(TRACE)?;
+        let (left, right) = function.call::<_, (Expr, Expr)>(())?;
+
+        Ok::<_, Error>(WaveProgram::new(left, right))
+    })
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use rlua::{Function, Lua, Table};
+
+    use crate::{
+        dsp::audio_graph::AUDIO_SIZE,
+        lua::{init_instance, load_fn},
+    };
+
+    use super::compile;
+
+    const WAVE: &str = include_str!("../../assets/lua/common/instrument/wave.lua");
+    const LUA_PULSE: &str = include_str!("../../assets/lua/nodes/instrument/lua_pulse/wave.lua");
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCKS: usize = 64;
+
+    fn load() -> Lua {
+        let lua = init_instance();
+        load_fn(&lua, "wave", WAVE);
+        load_fn(&lua, "lua_pulse", LUA_PULSE);
+        lua
+    }
+
+    fn lua_block(lua: &Lua, t0: f64, frequency: f32) -> Vec<(f32, f32)> {
+        lua.context(|ctx| {
+            let globals = ctx.globals();
+            let function: Function = globals.get("OUT_BLOCK_FN")?;
+            function.call::<_, ()>((AUDIO_SIZE, SAMPLE_RATE, t0, frequency))?;
+
+            let out_l: Table = globals.get("OUT_L")?;
+            let out_r: Table = globals.get("OUT_R")?;
+
+            (1..=AUDIO_SIZE)
+                .map(|i| Ok((out_l.get(i)?, out_r.get(i)?)))
+                .collect::<rlua::Result<Vec<_>>>()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn native_matches_lua() {
+        let mut program = compile(&load()).expect("lua_pulse wave should trace");
+        let lua = load();
+
+        let frequency = 220.0 * TAU;
+        let interval = 1.0 / SAMPLE_RATE as f64;
+
+        for block in 0..BLOCKS {
+            let t0 = (block * AUDIO_SIZE) as f64 * interval;
+            let expected = lua_block(&lua, t0, frequency);
+
+            for (i, (left, right)) in expected.into_iter().enumerate() {
+                let t = t0 + interval * i as f64;
+                let (out0, out1) = program.next(t, frequency, interval);
+
+                assert!(
+                    (out0 - left).abs() < 1e-5 && (out1 - right).abs() < 1e-5,
+                    "block {block}, sample {i}: native ({out0}, {out1}), lua ({left}, {right})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn branching_waves_fall_back() {
+        let branches = [
+            "if W(math.sin, f) == W(math.cos, f) then return 0.0, 0.0 end",
+            "if W(math.sin, f) < 0.0 then return 0.0, 0.0 end",
+            "if T >= 1.0 then return 0.0, 0.0 end",
+        ];
+
+        for branch in branches {
+            let lua = init_instance();
+            load_fn(&lua, "wave", WAVE);
+            load_fn(
+                &lua,
+                "branch",
+                &format!(
+                    "FN = function(frequency)\n\
+                         local f = frequency\n\
+                         {branch}\n\
+                         return W(math.sin, f), W(math.sin, f)\n\
+                     end"
+                ),
+            );
+
+            assert!(compile(&lua).is_none(), "traced a branching wave: {branch}");
+        }
+    }
+}
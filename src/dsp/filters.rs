@@ -0,0 +1,281 @@
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, TAU},
+    sync::{atomic::Ordering, Arc},
+};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_CUTOFF: f32 = 1000.0;
+pub const DEFAULT_RESONANCE: f32 = FRAC_1_SQRT_2;
+
+const MIN_CUTOFF: f32 = 10.0;
+const MIN_RESONANCE: f32 = 0.1;
+
+/// Biquad filter responses, coefficients from the RBJ audio eq cookbook.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+impl FilterKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lowpass" => Some(FilterKind::LowPass),
+            "highpass" => Some(FilterKind::HighPass),
+            "bandpass" => Some(FilterKind::BandPass),
+            "notch" => Some(FilterKind::Notch),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterKind::LowPass => "lowpass",
+            FilterKind::HighPass => "highpass",
+            FilterKind::BandPass => "bandpass",
+            FilterKind::Notch => "notch",
+        }
+    }
+
+    pub fn all() -> [FilterKind; 4] {
+        [
+            FilterKind::LowPass,
+            FilterKind::HighPass,
+            FilterKind::BandPass,
+            FilterKind::Notch,
+        ]
+    }
+}
+
+#[derive(Clone)]
+pub struct Filter {
+    pub kind: FilterKind,
+}
+
+/// normalized biquad coefficients.
+#[derive(Clone, Copy, Default)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    fn new(kind: FilterKind, cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let cutoff = cutoff.clamp(MIN_CUTOFF, sample_rate * 0.49);
+        let resonance = resonance.max(MIN_RESONANCE);
+
+        let w0 = TAU * cutoff / sample_rate;
+        let cos = w0.cos();
+        let alpha = w0.sin() / (2.0 * resonance);
+
+        let (b0, b1, b2) = match kind {
+            FilterKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            FilterKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0),
+            FilterKind::BandPass => (alpha, 0.0, -alpha),
+            FilterKind::Notch => (1.0, -2.0 * cos, 1.0),
+        };
+
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: (-2.0 * cos) / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+}
+
+pub struct FilterStream {
+    kind: FilterKind,
+
+    cutoff: Arc<AtomicF32>,
+    resonance: Arc<AtomicF32>,
+
+    // coefficients are only recomputed when a parameter changes.
+    last: (f32, f32, f32),
+    coefficients: Coefficients,
+
+    // transposed direct form II state, per channel.
+    z: [[f32; 2]; 2],
+}
+
+impl FilterStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let params = (
+            self.cutoff.load(Ordering::Relaxed),
+            self.resonance.load(Ordering::Relaxed),
+            sample_rate,
+        );
+
+        if params != self.last {
+            self.coefficients = Coefficients::new(self.kind, params.0, params.1, sample_rate);
+            self.last = params;
+        }
+
+        let c = self.coefficients;
+
+        (0..ctx.block_size()).for_each(|i| {
+            (0..2).for_each(|ch| {
+                let x = ctx.inputs.read(ch, i);
+                let z = &mut self.z[ch];
+
+                let y = c.b0 * x + z[0];
+                z[0] = c.b1 * x - c.a1 * y + z[1];
+                z[1] = c.b2 * x - c.a2 * y;
+
+                ctx.outputs.write(y, ch, i);
+            });
+        });
+    }
+}
+
+impl Gen for FilterStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct FilterControl {
+    cutoff: Arc<AtomicF32>,
+    resonance: Arc<AtomicF32>,
+}
+
+impl FilterControl {
+    pub fn set_cutoff(&self, cutoff_hz: f32) {
+        self.cutoff.store(cutoff_hz, Ordering::Relaxed);
+    }
+
+    /// Q of the filter, 0.707 is flat.
+    pub fn set_resonance(&self, resonance: f32) {
+        self.resonance.store(resonance, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Filter {
+    type Stream = FilterStream;
+    type Control = FilterControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let cutoff = Arc::new(AtomicF32::new(DEFAULT_CUTOFF));
+        let resonance = Arc::new(AtomicF32::new(DEFAULT_RESONANCE));
+
+        let control = FilterControl {
+            cutoff: cutoff.clone(),
+            resonance: resonance.clone(),
+        };
+
+        let stream = FilterStream {
+            kind: self.kind,
+
+            cutoff,
+            resonance,
+
+            last: (0.0, 0.0, 0.0),
+            coefficients: Coefficients::default(),
+
+            z: [[0.0; 2]; 2],
+        };
+
+        Some(AudioSendControl::Filter((stream, control)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+    use super::{Coefficients, FilterKind};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const CUTOFF: f32 = 1000.0;
+
+    /// magnitude of the transfer function at a frequency in hz.
+    fn gain(c: Coefficients, frequency: f32) -> f32 {
+        let w = TAU * frequency / SAMPLE_RATE;
+        let (cos1, sin1, cos2, sin2) = (w.cos(), w.sin(), (2.0 * w).cos(), (2.0 * w).sin());
+
+        let num = (
+            c.b0 + c.b1 * cos1 + c.b2 * cos2,
+            -(c.b1 * sin1 + c.b2 * sin2),
+        );
+        let den = (
+            1.0 + c.a1 * cos1 + c.a2 * cos2,
+            -(c.a1 * sin1 + c.a2 * sin2),
+        );
+
+        (num.0.hypot(num.1)) / (den.0.hypot(den.1))
+    }
+
+    fn coefficients(kind: FilterKind) -> Coefficients {
+        Coefficients::new(kind, CUTOFF, FRAC_1_SQRT_2, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn lowpass_response() {
+        let c = coefficients(FilterKind::LowPass);
+
+        assert!((gain(c, 0.0) - 1.0).abs() < 1e-4);
+        assert!((gain(c, CUTOFF) - FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!(gain(c, SAMPLE_RATE / 2.0) < 1e-4);
+    }
+
+    #[test]
+    fn highpass_response() {
+        let c = coefficients(FilterKind::HighPass);
+
+        assert!(gain(c, 0.0) < 1e-4);
+        assert!((gain(c, CUTOFF) - FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!((gain(c, SAMPLE_RATE / 2.0) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bandpass_and_notch_at_cutoff() {
+        let band = coefficients(FilterKind::BandPass);
+        let notch = coefficients(FilterKind::Notch);
+
+        assert!((gain(band, CUTOFF) - 1.0).abs() < 1e-3);
+        assert!(gain(band, 0.0) < 1e-4);
+
+        assert!(gain(notch, CUTOFF) < 1e-3);
+        assert!((gain(notch, 0.0) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parameters_are_clamped() {
+        let c = Coefficients::new(FilterKind::LowPass, SAMPLE_RATE, 0.0, SAMPLE_RATE);
+
+        assert!([c.b0, c.b1, c.b2, c.a1, c.a2].iter().all(|x| x.is_finite()));
+        // poles stay inside the unit circle.
+        assert!(c.a2.abs() < 1.0 && c.a1.abs() < 1.0 + c.a2);
+        assert!(gain(c, 0.0).is_finite());
+    }
+}
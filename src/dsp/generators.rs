@@ -0,0 +1,249 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_FREQUENCY: f32 = 220.0;
+pub const DEFAULT_AMPLITUDE: f32 = 0.5;
+pub const DEFAULT_WIDTH: f32 = 0.5;
+
+/// Built in generators, rendered natively instead of through lua.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneratorKind {
+    Sine,
+    Saw,
+    Square,
+    Triangle,
+    Pulse,
+    WhiteNoise,
+    PinkNoise,
+}
+
+impl GeneratorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sine" => Some(GeneratorKind::Sine),
+            "saw" => Some(GeneratorKind::Saw),
+            "square" => Some(GeneratorKind::Square),
+            "triangle" => Some(GeneratorKind::Triangle),
+            "pulse" => Some(GeneratorKind::Pulse),
+            "white_noise" => Some(GeneratorKind::WhiteNoise),
+            "pink_noise" => Some(GeneratorKind::PinkNoise),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorKind::Sine => "sine",
+            GeneratorKind::Saw => "saw",
+            GeneratorKind::Square => "square",
+            GeneratorKind::Triangle => "triangle",
+            GeneratorKind::Pulse => "pulse",
+            GeneratorKind::WhiteNoise => "white_noise",
+            GeneratorKind::PinkNoise => "pink_noise",
+        }
+    }
+
+    pub fn all() -> [GeneratorKind; 7] {
+        [
+            GeneratorKind::Sine,
+            GeneratorKind::Saw,
+            GeneratorKind::Square,
+            GeneratorKind::Triangle,
+            GeneratorKind::Pulse,
+            GeneratorKind::WhiteNoise,
+            GeneratorKind::PinkNoise,
+        ]
+    }
+}
+
+#[derive(Clone)]
+pub struct Generator {
+    pub kind: GeneratorKind,
+}
+
+pub struct GeneratorStream {
+    kind: GeneratorKind,
+
+    frequency: Arc<AtomicF32>,
+    amplitude: Arc<AtomicF32>,
+    width: Arc<AtomicF32>,
+
+    // oscillator state
+    phase: f32,
+    triangle: f32,
+
+    // noise state
+    seed: u32,
+    pink: [f32; 7],
+}
+
+impl GeneratorStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let frequency = self.frequency.load(Ordering::Relaxed);
+        let amplitude = self.amplitude.load(Ordering::Relaxed);
+        let width = self.width.load(Ordering::Relaxed).clamp(0.01, 0.99);
+
+        let dt = (frequency / sample_rate).clamp(0.0, 0.5);
+
+        (0..ctx.block_size()).for_each(|i| {
+            let out = self.next_sample(dt, width) * amplitude;
+
+            ctx.outputs.write(out, 0, i);
+            ctx.outputs.write(out, 1, i);
+        });
+    }
+
+    fn next_sample(&mut self, dt: f32, width: f32) -> f32 {
+        let t = self.phase;
+
+        let out = match self.kind {
+            GeneratorKind::Sine => (t * std::f32::consts::TAU).sin(),
+            GeneratorKind::Saw => (2.0 * t - 1.0) - poly_blep(t, dt),
+            GeneratorKind::Square => pulse(t, dt, 0.5),
+            GeneratorKind::Pulse => pulse(t, dt, width),
+            GeneratorKind::Triangle => {
+                // leaky integration of a band limited square
+                self.triangle = dt * pulse(t, dt, 0.5) + (1.0 - dt) * self.triangle;
+                self.triangle * 4.0
+            }
+            GeneratorKind::WhiteNoise => self.white(),
+            GeneratorKind::PinkNoise => self.pink(),
+        };
+
+        self.phase = (self.phase + dt).fract();
+
+        out
+    }
+
+    /// xorshift, good enough for audio and does not allocate.
+    fn white(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+
+        (self.seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// Paul Kellet's refined pink noise filter.
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        let b = &mut self.pink;
+
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+
+        let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+
+        out * 0.11
+    }
+}
+
+impl Gen for GeneratorStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct GeneratorControl {
+    frequency: Arc<AtomicF32>,
+    amplitude: Arc<AtomicF32>,
+    width: Arc<AtomicF32>,
+}
+
+impl GeneratorControl {
+    pub fn set_frequency(&self, frequency_hz: f32) {
+        self.frequency.store(frequency_hz, Ordering::Relaxed);
+    }
+
+    pub fn set_amplitude(&self, amplitude: f32) {
+        self.amplitude.store(amplitude, Ordering::Relaxed);
+    }
+
+    /// pulse width of the pulse generator, 0.0 to 1.0.
+    pub fn set_width(&self, width: f32) {
+        self.width.store(width, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Generator {
+    type Stream = GeneratorStream;
+    type Control = GeneratorControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let frequency = Arc::new(AtomicF32::new(DEFAULT_FREQUENCY));
+        let amplitude = Arc::new(AtomicF32::new(DEFAULT_AMPLITUDE));
+        let width = Arc::new(AtomicF32::new(DEFAULT_WIDTH));
+
+        let control = GeneratorControl {
+            frequency: frequency.clone(),
+            amplitude: amplitude.clone(),
+            width: width.clone(),
+        };
+
+        let stream = GeneratorStream {
+            kind: self.kind,
+
+            frequency,
+            amplitude,
+            width,
+
+            phase: 0.0,
+            triangle: 0.0,
+
+            seed: 0x9E3779B9,
+            pink: [0.0; 7],
+        };
+
+        Some(AudioSendControl::Generator((stream, control)))
+    }
+}
+
+/// polynomial band limited step, removes the aliasing of hard edges.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if dt <= 0.0 {
+        return 0.0;
+    }
+
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+fn pulse(t: f32, dt: f32, width: f32) -> f32 {
+    let naive = if t < width { 1.0 } else { -1.0 };
+
+    naive + poly_blep(t, dt) - poly_blep((t + 1.0 - width).fract(), dt)
+}
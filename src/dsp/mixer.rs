@@ -0,0 +1,106 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use atomic_float::AtomicF32;
+use bevy::ecs::entity::Entity;
+use knyst::{
+    gen::Gen,
+    graph::NodeId,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+/// stereo inputs of a mixer.
+pub const MIXER_INPUTS: usize = 4;
+pub const DEFAULT_GAIN: f32 = 1.0;
+
+#[derive(Clone)]
+pub struct Mixer;
+
+/// Tail of a chain that feeds one input of a mixer on another chain.
+/// `node` is resolved from the built mixer before the chain is streamed.
+#[derive(Clone)]
+pub struct BusSend {
+    pub bus: Entity,
+    pub input: usize,
+    pub node: Option<NodeId>,
+}
+
+pub struct MixerStream {
+    gains: [Arc<AtomicF32>; MIXER_INPUTS],
+}
+
+impl MixerStream {
+    fn generate_samples(&mut self, ctx: GenContext) {
+        let gains = self.gains.each_ref().map(|g| g.load(Ordering::Relaxed));
+
+        (0..ctx.block_size()).for_each(|i| {
+            let mut out = [0.0; 2];
+
+            gains.iter().enumerate().for_each(|(input, gain)| {
+                out[0] += ctx.inputs.read(input * 2, i) * gain;
+                out[1] += ctx.inputs.read(input * 2 + 1, i) * gain;
+            });
+
+            ctx.outputs.write(out[0], 0, i);
+            ctx.outputs.write(out[1], 1, i);
+        });
+    }
+}
+
+impl Gen for MixerStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        self.generate_samples(ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        MIXER_INPUTS * 2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct MixerControl {
+    gains: [Arc<AtomicF32>; MIXER_INPUTS],
+}
+
+impl MixerControl {
+    pub fn set_gain(&self, input: usize, gain: f32) {
+        if let Some(g) = self.gains.get(input) {
+            g.store(gain, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Streamable for Mixer {
+    type Stream = MixerStream;
+    type Control = MixerControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let gains = [(); MIXER_INPUTS].map(|_| Arc::new(AtomicF32::new(DEFAULT_GAIN)));
+
+        let control = MixerControl {
+            gains: gains.clone(),
+        };
+
+        let stream = MixerStream { gains };
+
+        Some(AudioSendControl::Mixer((stream, control)))
+    }
+}
+
+impl BusSend {
+    /// waits until the mixer it feeds has been built.
+    pub fn to_stream(&self) -> Option<AudioSendControl> {
+        self.node
+            .map(|node| AudioSendControl::Bus(node, self.input))
+    }
+}
@@ -1,40 +1,236 @@
 use bevy::ecs::entity::Entity;
+
 use knyst::graph::NodeId;
 
 use self::{
+    audio_graph::{DspAssets, Streamable},
+    delay::{Delay, DelayControl, DelayStream},
+    envelope::{Envelope, EnvelopeControl, EnvelopeStream},
+    filters::{Filter, FilterControl, FilterStream},
+    generators::{Generator, GeneratorControl, GeneratorStream},
+    mixer::{BusSend, Mixer, MixerControl, MixerStream},
     oscillators::{Oscillator, OscillatorControl, OscillatorStream},
+    pan::{Pan, PanControl, PanStream},
     read::{Read, ReadControl, ReadStream},
+    reverb::{Reverb, ReverbControl, ReverbStream},
+    sampler::{Sampler, SamplerControl, SamplerStream},
+    wavetable::{Wavetable, WavetableControl, WavetableStream},
 };
 
 pub mod audio_graph;
+pub mod backend;
+pub mod delay;
+pub mod envelope;
+pub mod expression;
+pub mod filters;
+pub mod generators;
+pub mod mixer;
 pub mod oscillators;
+pub mod pan;
+pub mod profiler;
 pub mod read;
+pub mod realtime;
+pub mod render;
+pub mod reverb;
+pub mod sampler;
+pub mod voices;
+pub mod wavetable;
 
 #[derive(Clone)]
 pub enum Dsp {
     Input(Oscillator),
+    Generator(Generator),
+    Sampler(Sampler),
+    Wavetable(Wavetable),
+    Filter(Filter),
+    Envelope(Envelope),
+    Delay(Delay),
+    Reverb(Reverb),
+    Pan(Pan),
+    Mixer(Mixer),
+    BusSend(BusSend),
     Read(Read),
     Output,
 }
 
+impl Dsp {
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_stream(&mut self, assets: &DspAssets) -> Option<AudioSendControl> {
+        match self {
+            Dsp::Input(i) => i.to_stream(assets),
+            Dsp::Generator(i) => i.to_stream(assets),
+            Dsp::Sampler(i) => i.to_stream(assets),
+            Dsp::Wavetable(i) => i.to_stream(assets),
+            Dsp::Filter(i) => i.to_stream(assets),
+            Dsp::Envelope(i) => i.to_stream(assets),
+            Dsp::Delay(i) => i.to_stream(assets),
+            Dsp::Reverb(i) => i.to_stream(assets),
+            Dsp::Pan(i) => i.to_stream(assets),
+            Dsp::Mixer(i) => i.to_stream(assets),
+            Dsp::BusSend(i) => i.to_stream(),
+            Dsp::Read(i) => i.to_stream(assets),
+            Dsp::Output => Some(AudioSendControl::Output),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum AudioSendControl {
     Read((ReadStream, ReadControl)),
     Oscillator((OscillatorStream, OscillatorControl)),
+    Generator((GeneratorStream, GeneratorControl)),
+    Sampler((SamplerStream, SamplerControl)),
+    Wavetable((WavetableStream, WavetableControl)),
+    Filter((FilterStream, FilterControl)),
+    Envelope((EnvelopeStream, EnvelopeControl)),
+    Delay((DelayStream, DelayControl)),
+    Reverb((ReverbStream, ReverbControl)),
+    Pan((PanStream, PanControl)),
+    Mixer((MixerStream, MixerControl)),
+    Bus(NodeId, usize),
     Output,
 }
 
 pub enum AudioControl {
     Read(ReadControl),
     Oscillator(OscillatorControl),
+    Generator(GeneratorControl),
+    Sampler(SamplerControl),
+    Wavetable(WavetableControl),
+    Filter(FilterControl),
+    Envelope(EnvelopeControl),
+    Delay(DelayControl),
+    Reverb(ReverbControl),
+    Pan(PanControl),
+    Mixer(MixerControl),
+    Bus,
     Output,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum AudioSend {
     Read(ReadStream),
     Oscillator(OscillatorStream),
+    Generator(GeneratorStream),
+    Sampler(SamplerStream),
+    Wavetable(WavetableStream),
+    Filter(FilterStream),
+    Envelope(EnvelopeStream),
+    Delay(DelayStream),
+    Reverb(ReverbStream),
+    Pan(PanStream),
+    Mixer(MixerStream),
+    Bus(NodeId, usize),
     Output,
 }
 
-pub struct Chain {
-    pub items: Vec<Dsp>,
+impl AudioSendControl {
+    pub fn split(self) -> (AudioSend, AudioControl) {
+        match self {
+            AudioSendControl::Read((stream, control)) => {
+                (AudioSend::Read(stream), AudioControl::Read(control))
+            }
+            AudioSendControl::Oscillator((stream, control)) => (
+                AudioSend::Oscillator(stream),
+                AudioControl::Oscillator(control),
+            ),
+            AudioSendControl::Generator((stream, control)) => (
+                AudioSend::Generator(stream),
+                AudioControl::Generator(control),
+            ),
+            AudioSendControl::Sampler((stream, control)) => {
+                (AudioSend::Sampler(stream), AudioControl::Sampler(control))
+            }
+            AudioSendControl::Wavetable((stream, control)) => (
+                AudioSend::Wavetable(stream),
+                AudioControl::Wavetable(control),
+            ),
+            AudioSendControl::Filter((stream, control)) => {
+                (AudioSend::Filter(stream), AudioControl::Filter(control))
+            }
+            AudioSendControl::Envelope((stream, control)) => {
+                (AudioSend::Envelope(stream), AudioControl::Envelope(control))
+            }
+            AudioSendControl::Delay((stream, control)) => {
+                (AudioSend::Delay(stream), AudioControl::Delay(control))
+            }
+            AudioSendControl::Reverb((stream, control)) => {
+                (AudioSend::Reverb(stream), AudioControl::Reverb(control))
+            }
+            AudioSendControl::Pan((stream, control)) => {
+                (AudioSend::Pan(stream), AudioControl::Pan(control))
+            }
+            AudioSendControl::Mixer((stream, control)) => {
+                (AudioSend::Mixer(stream), AudioControl::Mixer(control))
+            }
+            AudioSendControl::Bus(node, input) => (AudioSend::Bus(node, input), AudioControl::Bus),
+            AudioSendControl::Output => (AudioSend::Output, AudioControl::Output),
+        }
+    }
+}
+
+// Chain
+/// TChain - a link in the audio chain tree.
+/// Every link is keyed by the grid entity that created it.
+pub struct TChain {
+    pub t: Box<ChainType>,
+    pub entity: Option<Entity>,
+}
+
+/// Type of a chain link, either a list of sub chains or a single dsp item.
+pub enum ChainType {
+    ChainList(Vec<TChain>),
+    Dsp(Dsp),
+}
+
+impl TChain {
+    pub fn vec(items: Vec<TChain>, entity: Option<Entity>) -> Self {
+        Self {
+            t: Box::new(ChainType::ChainList(items)),
+            entity,
+        }
+    }
+
+    pub fn dsp(dsp: Dsp, entity: Option<Entity>) -> Self {
+        Self {
+            t: Box::new(ChainType::Dsp(dsp)),
+            entity,
+        }
+    }
+
+    /// checks if any link in this chain was created by the entity.
+    pub fn contains(&self, entity: Entity) -> bool {
+        if self.entity == Some(entity) {
+            return true;
+        }
+
+        match self.t.as_ref() {
+            ChainType::ChainList(l) => l.iter().any(|c| c.contains(entity)),
+            ChainType::Dsp(_) => false,
+        }
+    }
+
+    /// checks if the chain ends in an audio output.
+    pub fn is_terminated(&self) -> bool {
+        self.flatten()
+            .last()
+            .is_some_and(|(dsp, _)| matches!(dsp, Dsp::Output))
+    }
+
+    /// walks the tree depth first, returning the dsp items in the order they are processed.
+    pub fn flatten(&self) -> Vec<(&Dsp, Option<Entity>)> {
+        match self.t.as_ref() {
+            ChainType::ChainList(l) => l.iter().flat_map(|c| c.flatten()).collect(),
+            ChainType::Dsp(dsp) => vec![(dsp, self.entity)],
+        }
+    }
+
+    pub fn flatten_mut(&mut self) -> Vec<(&mut Dsp, Option<Entity>)> {
+        let entity = self.entity;
+
+        match self.t.as_mut() {
+            ChainType::ChainList(l) => l.iter_mut().flat_map(|c| c.flatten_mut()).collect(),
+            ChainType::Dsp(dsp) => vec![(dsp, entity)],
+        }
+    }
 }
@@ -1,105 +1,585 @@
-use core::panic;
 use std::{
     f32::consts::TAU,
-    sync::{atomic::Ordering, Arc},
-    time::{Duration, Instant},
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, Thread},
 };
 
 use atomic_float::AtomicF32;
 use bevy::{
     asset::{Assets, Handle},
     ecs::system::Res,
-    log::{info, trace},
+    log::{error, info, warn},
 };
 use knyst::{
     gen::Gen,
     prelude::{GenContext, GenState},
     Resources,
 };
-use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
-use rlua::{Function, Lua, Result};
+use rlua::{Error, Function, Lua, Result, Table};
+use rtrb::{Consumer, Producer, PushError, RingBuffer};
 
 use crate::{
     components::lua::LuaAsset,
-    lua::{init_instance, load_fn},
-    FREQUENCY_TEMP,
+    lua::{init_instance, try_load_fn},
 };
 
 use super::{
-    audio_graph::{Streamable, AUDIO_SIZE},
+    audio_graph::{DspAssets, Streamable, AUDIO_SIZE},
+    expression::{compile, WaveProgram},
+    voices::{VoiceEnvelope, VoicesControl, MAX_VOICES},
     AudioSendControl,
 };
 
-const OUT: &str = "OUT_FN";
+/// frequency until the node's slot is read, in Hz.
+pub const DEFAULT_FREQUENCY: f32 = 80.0;
+/// crossfade into an edited wave, in seconds.
+pub const DEFAULT_WAVE_FADE: f32 = 0.05;
+
+// level of a single voice, leaves headroom for chords.
+const VOICE_GAIN: f32 = 0.5;
+
+// blocks the lua worker renders ahead of the audio thread.
+const LUA_AHEAD_BLOCKS: usize = 4;
+// edited waves waiting to be faded in, and replaced ones waiting to be freed.
+const SWAP_QUEUE: usize = 4;
+
+const OUT: &str = "OUT_BLOCK_FN";
+const OUT_L: &str = "OUT_L";
+const OUT_R: &str = "OUT_R";
+
+// scripts of an edited wave and the crossfade into it in seconds.
+type WaveEdit = (Vec<WaveScript>, f32);
+
+/// A `wave.lua` with the asset path it was loaded from.
+#[derive(Clone)]
+pub struct WaveScript {
+    pub path: String,
+    pub source: String,
+}
+
+/// A lua error of a wave, located in its script when lua names a line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveError {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl WaveError {
+    /// splits `file:line: message`, the first line of the error.
+    fn from_lua(error: &Error) -> Self {
+        let text = match error {
+            Error::RuntimeError(message) => message.clone(),
+            Error::SyntaxError { message, .. } => message.clone(),
+            Error::CallbackError { cause, .. } => cause.to_string(),
+            error => error.to_string(),
+        };
+        let first = text.lines().next().unwrap_or_default();
+
+        let mut parts = first.splitn(3, ':');
+        match (
+            parts.next(),
+            parts
+                .next()
+                .and_then(|line| line.trim().parse::<u32>().ok()),
+            parts.next(),
+        ) {
+            (Some(file), Some(line), Some(message)) => Self {
+                file: Some(file.to_string()),
+                line: Some(line),
+                message: message.trim().to_string(),
+            },
+            _ => Self {
+                file: None,
+                line: None,
+                message: first.to_string(),
+            },
+        }
+    }
+}
+
+/// What the engines of an oscillator send back to bevy.
+#[derive(Clone, Debug)]
+pub enum WaveReport {
+    /// a new engine was built, errors before it are stale.
+    Reloaded,
+    Error(WaveError),
+}
 
 #[derive(Clone)]
 pub struct Oscillator {
     pub lua_handle: Vec<Handle<LuaAsset>>,
+    #[allow(dead_code)]
     pub lua_string: String,
 }
 
-pub struct OscillatorStream {
+/// how the wave script is run, traced scripts are evaluated natively.
+#[allow(clippy::large_enum_variant)]
+pub enum WaveRuntime {
+    Native(WaveProgram),
+    Lua(Box<Lua>),
+}
+
+/// The voices of an instrument, rendered a block at a time.
+pub struct WaveVoices {
     frequency: Arc<AtomicF32>,
-    luas: [Box<Lua>; AUDIO_SIZE],
+    voice_count: Arc<AtomicUsize>,
 
-    // duration stuff
-    duration: [Duration; AUDIO_SIZE],
-    duration_idx: usize,
+    // one runtime per voice, the first one also plays free running.
+    runtimes: Vec<WaveRuntime>,
+    envelopes: Vec<VoiceEnvelope>,
+
+    // samples rendered since the stream started.
+    position: u64,
+
+    errors: Sender<WaveReport>,
+    // an error is only sent again once a different one happened in between.
+    last_error: Option<String>,
 }
 
-impl OscillatorStream {
-    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
-        let interval = 1.0 / sample_rate;
+impl WaveVoices {
+    /// renders `out.len()` frames, every frame is overwritten.
+    fn render(&mut self, sample_rate: f32, out: &mut [(f32, f32)]) {
+        // seconds since the stream started, f64 so long playback keeps its precision.
+        let t0 = self.position as f64 / sample_rate as f64;
+        let block_size = out.len();
+
+        self.position += block_size as u64;
+
+        // without voices the instrument plays one wave at the frequency slot.
+        if self.voice_count.load(Ordering::Relaxed) == 0 {
+            let frequency = self.frequency.load(Ordering::Relaxed);
+
+            let res = render(
+                &mut self.runtimes[0],
+                block_size,
+                sample_rate,
+                t0,
+                frequency,
+                |i, frame| out[i] = frame,
+            );
+
+            if let Err(e) = res {
+                self.report(&e);
+            }
+            return;
+        }
+
+        out.iter_mut().for_each(|frame| *frame = (0.0, 0.0));
+        let mut failed = None;
 
-        let frequency = self.frequency.load(Ordering::Relaxed);
+        // voices beyond the count still play out their release.
+        for (runtime, envelope) in self.runtimes.iter_mut().zip(self.envelopes.iter_mut()) {
+            envelope.update(sample_rate);
 
-        let t_size = interval * (ctx.block_size() as f32);
+            if envelope.is_active() {
+                let frequency = envelope.frequency();
 
-        (0..ctx.block_size())
-            .into_par_iter()
-            .zip(&mut self.luas)
-            .map(|(i, lua)| {
-                let t = interval * i as f32;
-                let default: (f32, f32) = (0.0, 0.0);
+                let res = render(
+                    runtime,
+                    block_size,
+                    sample_rate,
+                    t0,
+                    frequency,
+                    |i, (out0, out1)| {
+                        let level = envelope.next(sample_rate) * VOICE_GAIN;
 
-                match call_lua(&lua, t, frequency, t_size) {
-                    Ok(out) => (i, out),
-                    Err(_) => (i, default),
+                        out[i].0 += out0 * level;
+                        out[i].1 += out1 * level;
+                    },
+                );
+
+                if let Err(e) = res {
+                    failed = Some(e);
                 }
-            })
-            .collect::<Vec<(usize, (f32, f32))>>()
-            .into_iter()
-            .for_each(|(i, (out0, out1))| {
-                let out0 = norm(out0);
-                let out1 = norm(out1);
-
-                ctx.outputs.write(out0, 0, i);
-                ctx.outputs.write(out1, 1, i);
+            }
+
+            envelope.finish();
+        }
+
+        if let Some(e) = failed {
+            self.report(&e);
+        }
+
+        out.iter_mut()
+            .for_each(|frame| *frame = (norm(frame.0), norm(frame.1)));
+    }
+
+    /// sends a lua error to the control, native waves never get here.
+    fn report(&mut self, error: &Error) {
+        let error = WaveError::from_lua(error);
+
+        if self.last_error.as_ref() != Some(&error.message) {
+            self.last_error = Some(error.message.clone());
+            let _ = self.errors.send(WaveReport::Error(error));
+        }
+    }
+}
+
+/// Wakes the worker of an oscillator, and once more when dropped so it notices what went away.
+/// Kept as the last field, everything before it is already dropped by then.
+struct WorkerWaker(Thread);
+
+impl WorkerWaker {
+    fn wake(&self) {
+        self.0.unpark();
+    }
+}
+
+impl Drop for WorkerWaker {
+    fn drop(&mut self) {
+        self.0.unpark();
+    }
+}
+
+/// A lua wave on the worker, rendered into the ring its engine reads from.
+struct LuaJob {
+    voices: WaveVoices,
+    producer: Producer<(f32, f32)>,
+}
+
+impl LuaJob {
+    /// renders blocks until the ring is full.
+    fn fill(&mut self, sample_rate: f32, block: &mut [(f32, f32)]) {
+        while self.producer.slots() >= block.len() {
+            self.voices.render(sample_rate, block);
+
+            // room was checked above, the worker is the only producer.
+            block.iter().for_each(|frame| {
+                let _ = self.producer.push(*frame);
             });
+        }
     }
 }
 
-impl Gen for OscillatorStream {
-    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
-        let start = Instant::now();
+/// What the worker of an oscillator is handed.
+enum WorkerJob {
+    /// voices of a lua wave to render ahead.
+    Render(LuaJob),
+    /// an edited wave for the stream to fade into, over the given seconds.
+    Swap(WaveEngine, f32),
+}
 
-        let sample_rate = ctx.sample_rate;
-        self.generate_samples(sample_rate, ctx);
+/// One thread per oscillator doing what its stream can not: running its lua waves, rlua allocates
+/// on every call, and freeing the engines it faded out of.
+/// It renders a few blocks ahead into rings the streams read from, then sleeps until it is woken.
+struct WaveWorker {
+    jobs: Sender<WorkerJob>,
+    waker: WorkerWaker,
+}
+
+impl WaveWorker {
+    /// the worker waits until the stream has published its sample rate.
+    /// It stops with the stream, once the stream dropped its end of `retired`.
+    fn spawn(
+        sample_rate: Arc<AtomicF32>,
+        mut swaps: Producer<(WaveEngine, f32)>,
+        mut retired: Consumer<WaveEngine>,
+    ) -> Option<Self> {
+        let (jobs, receiver) = mpsc::channel::<WorkerJob>();
+
+        let spawned = thread::Builder::new()
+            .name("wave worker".to_string())
+            .spawn(move || {
+                let mut waves: Vec<LuaJob> = vec![];
+                let mut block = vec![(0.0, 0.0); AUDIO_SIZE];
+
+                loop {
+                    while let Ok(job) = receiver.try_recv() {
+                        match job {
+                            WorkerJob::Render(wave) => waves.push(wave),
+                            WorkerJob::Swap(engine, fade) => {
+                                if swaps.push((engine, fade)).is_err() {
+                                    warn!("too many wave edits in flight, skipping one");
+                                }
+                            }
+                        }
+                    }
+
+                    while let Ok(engine) = retired.pop() {
+                        drop(engine);
+                    }
+
+                    if retired.is_abandoned() {
+                        return;
+                    }
+
+                    // an engine that was freed took its consumer with it.
+                    waves.retain(|wave| !wave.producer.is_abandoned());
+
+                    let sample_rate = sample_rate.load(Ordering::Relaxed);
+                    if sample_rate > 0.0 {
+                        waves
+                            .iter_mut()
+                            .for_each(|wave| wave.fill(sample_rate, &mut block));
+                    }
+
+                    thread::park();
+                }
+            });
+
+        match spawned {
+            Ok(handle) => Some(Self {
+                jobs,
+                waker: WorkerWaker(handle.thread().clone()),
+            }),
+            Err(e) => {
+                error!("failed to start wave worker - {}", e);
+                None
+            }
+        }
+    }
+
+    fn waker(&self) -> WorkerWaker {
+        WorkerWaker(self.waker.0.clone())
+    }
+
+    fn send(&self, job: WorkerJob) -> bool {
+        let sent = self.jobs.send(job).is_ok();
+        self.waker.wake();
+
+        sent
+    }
 
-        let duration = start.elapsed();
-        self.duration[self.duration_idx] = duration;
+    /// hands the voices of a lua wave to the worker, the returned engine plays what it renders.
+    fn render(&self, voices: WaveVoices) -> Option<LuaEngine> {
+        let (producer, consumer) = RingBuffer::new(LUA_AHEAD_BLOCKS * AUDIO_SIZE);
 
-        trace!("Time elapsed in expensive_function() is: {:?}", duration);
-        trace!(
-            "Average time elapsed: {:?}",
-            self.duration.iter().sum::<Duration>() / AUDIO_SIZE as u32
-        );
+        if !self.send(WorkerJob::Render(LuaJob { voices, producer })) {
+            error!("wave worker stopped, can not play lua wave");
+            return None;
+        }
+
+        Some(LuaEngine { consumer })
+    }
 
-        self.duration_idx += 1;
+    /// queues an edited wave, the stream fades into it over `fade` seconds.
+    fn swap(&self, engine: WaveEngine, fade: f32) {
+        // without a worker the stream is gone as well, the engine is dropped with the job.
+        self.send(WorkerJob::Swap(engine, fade));
+    }
+}
 
-        if self.duration_idx == AUDIO_SIZE {
-            self.duration_idx = 0;
+impl Clone for WaveWorker {
+    fn clone(&self) -> Self {
+        Self {
+            jobs: self.jobs.clone(),
+            waker: self.waker(),
         }
+    }
+}
+
+/// A lua wave rendered by the worker of its oscillator.
+pub struct LuaEngine {
+    consumer: Consumer<(f32, f32)>,
+}
+
+impl LuaEngine {
+    /// reads the rendered frames, a late worker plays silence.
+    fn read(&mut self, out: &mut [(f32, f32)]) {
+        out.iter_mut()
+            .for_each(|frame| *frame = self.consumer.pop().unwrap_or((0.0, 0.0)));
+    }
+}
+
+/// A built wave, swapped into a running stream when its script is edited.
+pub enum WaveEngine {
+    Native(WaveVoices),
+    Lua(LuaEngine),
+}
+
+impl WaveEngine {
+    /// traces the wave scripts to native code, lua runs them when that fails.
+    /// Errors of the scripts are reported after `WaveReport::Reloaded`.
+    fn build(
+        scripts: &[WaveScript],
+        shared: WaveShared,
+        envelopes: Vec<VoiceEnvelope>,
+        worker: &WaveWorker,
+    ) -> Option<Self> {
+        let (lua, load_errors) = load_wave(scripts);
+
+        let _ = shared.errors.send(WaveReport::Reloaded);
+        load_errors.iter().for_each(|e| {
+            let _ = shared
+                .errors
+                .send(WaveReport::Error(WaveError::from_lua(e)));
+        });
+
+        let (runtimes, native) = match compile(&lua) {
+            Some(program) => {
+                info!("wave compiled to native - {} ops", program.len());
+
+                let runtimes = (0..MAX_VOICES)
+                    .map(|_| WaveRuntime::Native(program.clone()))
+                    .collect();
+
+                (runtimes, true)
+            }
+            None => {
+                info!("wave could not be traced, falling back to lua");
+
+                // every voice keeps its own phases, so every voice needs its own instance.
+                let runtimes = (0..MAX_VOICES)
+                    .map(|_| WaveRuntime::Lua(Box::new(load_wave(scripts).0)))
+                    .collect();
+
+                (runtimes, false)
+            }
+        };
+
+        let voices = WaveVoices {
+            frequency: shared.frequency,
+            voice_count: shared.voice_count,
+
+            runtimes,
+            envelopes,
+
+            position: 0,
+
+            errors: shared.errors,
+            last_error: None,
+        };
+
+        match native {
+            true => Some(WaveEngine::Native(voices)),
+            false => Some(WaveEngine::Lua(worker.render(voices)?)),
+        }
+    }
+
+    fn render(&mut self, sample_rate: f32, out: &mut [(f32, f32)]) {
+        match self {
+            WaveEngine::Native(voices) => voices.render(sample_rate, out),
+            WaveEngine::Lua(engine) => engine.read(out),
+        }
+    }
+
+    /// a lua engine is ready once its worker has rendered a block.
+    fn is_ready(&self) -> bool {
+        match self {
+            WaveEngine::Native(_) => true,
+            WaveEngine::Lua(engine) => engine.consumer.slots() >= AUDIO_SIZE,
+        }
+    }
+
+    fn is_lua(&self) -> bool {
+        matches!(self, WaveEngine::Lua(_))
+    }
+}
+
+/// State every engine of an oscillator plays from, so a swapped engine keeps the pitch and voices.
+#[derive(Clone)]
+struct WaveShared {
+    frequency: Arc<AtomicF32>,
+    voice_count: Arc<AtomicUsize>,
+    // published by the stream, the worker waits for it.
+    sample_rate: Arc<AtomicF32>,
+    errors: Sender<WaveReport>,
+}
+
+pub struct OscillatorStream {
+    engine: WaveEngine,
+    // knyst blocks are rendered in chunks of this size, so it never grows.
+    block: Vec<(f32, f32)>,
+    sample_rate: Arc<AtomicF32>,
+
+    // an edited wave fades in while the playing one fades out.
+    swaps: Consumer<(WaveEngine, f32)>,
+    incoming: Option<WaveEngine>,
+    incoming_block: Vec<(f32, f32)>,
+    fade_position: usize,
+    fade_length: usize,
+    // replaced engines go back to the worker, it frees them.
+    retired: Producer<WaveEngine>,
+
+    waker: WorkerWaker,
+}
+
+impl OscillatorStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let block_size = ctx.block_size();
+        let mut offset = 0;
+
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+
+        while offset < block_size {
+            let len = (block_size - offset).min(self.block.len());
+
+            self.render_chunk(sample_rate, len);
+
+            self.block[..len]
+                .iter()
+                .enumerate()
+                .for_each(|(i, (out0, out1))| {
+                    ctx.outputs.write(*out0, 0, offset + i);
+                    ctx.outputs.write(*out1, 1, offset + i);
+                });
+
+            offset += len;
+        }
+
+        // lua engines are rendered ahead, the worker refills what was read.
+        if self.engine.is_lua() || self.incoming.as_ref().is_some_and(WaveEngine::is_lua) {
+            self.waker.wake();
+        }
+    }
+
+    /// renders `len` frames into `block`, crossfading into an incoming engine.
+    fn render_chunk(&mut self, sample_rate: f32, len: usize) {
+        if self.incoming.is_none() {
+            if let Ok((engine, fade)) = self.swaps.pop() {
+                self.incoming = Some(engine);
+                self.fade_position = 0;
+                self.fade_length = ((fade * sample_rate) as usize).max(1);
+            }
+        }
+
+        let block = &mut self.block[..len];
+        self.engine.render(sample_rate, block);
+
+        let incoming = match self.incoming.as_mut() {
+            Some(incoming) if incoming.is_ready() => incoming,
+            _ => return,
+        };
+
+        let incoming_block = &mut self.incoming_block[..len];
+        incoming.render(sample_rate, incoming_block);
+
+        // linear, edits of a wave mostly stay in phase with the old one.
+        for (out, new) in block.iter_mut().zip(incoming_block.iter()) {
+            let t = (self.fade_position as f32 / self.fade_length as f32).min(1.0);
+
+            out.0 += (new.0 - out.0) * t;
+            out.1 += (new.1 - out.1) * t;
+
+            self.fade_position += 1;
+        }
+
+        if self.fade_position >= self.fade_length {
+            if let Some(incoming) = self.incoming.take() {
+                let old = mem::replace(&mut self.engine, incoming);
+
+                // dropping it here would free it on the audio thread.
+                // The worker has not freed the last ones yet, both keep playing until it did.
+                if let Err(PushError::Full(old)) = self.retired.push(old) {
+                    self.incoming = Some(mem::replace(&mut self.engine, old));
+                }
+
+                self.waker.wake();
+            }
+        }
+    }
+}
+
+impl Gen for OscillatorStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
 
         GenState::Continue
     }
@@ -114,21 +594,83 @@ impl Gen for OscillatorStream {
 }
 
 pub struct OscillatorControl {
-    frequency: Arc<AtomicF32>,
-    time: Arc<AtomicF32>,
+    shared: WaveShared,
+    voices: Arc<VoicesControl>,
+    worker: WaveWorker,
+
+    // edits for the swap thread, started with the first one.
+    edits: Mutex<Option<Sender<WaveEdit>>>,
+
+    // only locked from bevy, see `reports`.
+    reports: Mutex<Receiver<WaveReport>>,
 }
 
 impl OscillatorControl {
-    pub fn frequency(&self) -> f32 {
-        self.frequency.load(Ordering::Relaxed) / TAU
+    pub fn set_frequency(&self, frequency_hz: f32) {
+        self.shared
+            .frequency
+            .store(frequency_hz * TAU, Ordering::Relaxed);
     }
 
-    pub fn set_frequency(&self, frequency_hz: f32) {
-        self.frequency.store(frequency_hz * TAU, Ordering::Relaxed);
+    /// notes played on top of the free running wave, see `VoicesControl`.
+    pub fn voices(&self) -> &VoicesControl {
+        &self.voices
+    }
+
+    /// drains what the engines reported since the last call, in order.
+    pub fn reports(&self) -> Vec<WaveReport> {
+        self.reports.lock().unwrap().try_iter().collect()
+    }
+
+    /// Queues the edited wave for the swap thread, the stream then fades into it over `fade` seconds.
+    /// Edits saved while one is building replace each other, only the newest one is built.
+    /// The worker frees the replaced engine, the rest of the chain keeps playing.
+    pub fn hot_swap(&self, scripts: Vec<WaveScript>, fade: f32) {
+        let mut edits = self.edits.lock().unwrap();
+
+        if edits.is_none() {
+            *edits = self.spawn_swap_thread();
+        }
+
+        if let Some(sender) = edits.as_ref() {
+            // the thread stopped, the next edit starts a new one.
+            if sender.send((scripts, fade)).is_err() {
+                warn!("wave swap thread stopped, edit dropped");
+                *edits = None;
+            }
+        }
     }
 
-    pub fn set_time(&self, time: f32) {
-        self.time.store(time * TAU, Ordering::Relaxed);
+    fn spawn_swap_thread(&self) -> Option<Sender<WaveEdit>> {
+        let (sender, edits) = mpsc::channel::<WaveEdit>();
+        let shared = self.shared.clone();
+        let voices = self.voices.clone();
+        let worker = self.worker.clone();
+
+        // runs until the control is dropped.
+        let spawned = thread::Builder::new()
+            .name("wave swap".to_string())
+            .spawn(move || {
+                while let Ok(edit) = edits.recv() {
+                    let (scripts, fade) = edits.try_iter().last().unwrap_or(edit);
+                    // voices as they play now, notes started while building carry over.
+                    let envelopes = voices.envelopes();
+
+                    if let Some(engine) =
+                        WaveEngine::build(&scripts, shared.clone(), envelopes, &worker)
+                    {
+                        worker.swap(engine, fade);
+                    }
+                }
+            });
+
+        match spawned {
+            Ok(_) => Some(sender),
+            Err(e) => {
+                error!("failed to start wave swap - {}", e);
+                None
+            }
+        }
     }
 }
 
@@ -139,74 +681,163 @@ impl Streamable for Oscillator {
     fn to_stream(
         &mut self,
         // _knyst: &mut KnystCommands,
-        lua_assets: &Res<Assets<LuaAsset>>,
+        assets: &DspAssets,
     ) -> Option<AudioSendControl> {
-        let mut error = false;
-
-        let luas = [(); AUDIO_SIZE].map(|_| {
-            let lua = init_instance();
-
-            self.lua_handle.iter().for_each(|handle| {
-                let asset = lua_assets.get(handle.clone());
+        let scripts = self.scripts(&assets.lua)?;
+        let (voices, envelopes) = VoicesControl::new();
+        let (errors, reports) = mpsc::channel();
+
+        let shared = WaveShared {
+            frequency: Arc::new(AtomicF32::new(DEFAULT_FREQUENCY * TAU)),
+            voice_count: voices.count_handle(),
+            sample_rate: Arc::new(AtomicF32::new(0.0)),
+            errors,
+        };
 
-                if asset.is_none() {
-                    error = true;
-                    return;
-                }
+        let (swap_producer, swaps) = RingBuffer::new(SWAP_QUEUE);
+        let (retired, retired_consumer) = RingBuffer::new(SWAP_QUEUE);
 
-                let asset = asset.unwrap();
+        let worker =
+            WaveWorker::spawn(shared.sample_rate.clone(), swap_producer, retired_consumer)?;
+        let engine = WaveEngine::build(&scripts, shared.clone(), envelopes, &worker)?;
 
-                load_fn(&lua, "lua_pulse", &asset.script);
-            });
+        let stream = OscillatorStream {
+            engine,
+            block: vec![(0.0, 0.0); AUDIO_SIZE],
+            sample_rate: shared.sample_rate.clone(),
+
+            swaps,
+            incoming: None,
+            incoming_block: vec![(0.0, 0.0); AUDIO_SIZE],
+            fade_position: 0,
+            fade_length: 0,
+            retired,
+
+            waker: worker.waker(),
+        };
 
-            Box::new(lua)
-        });
+        let control = OscillatorControl {
+            shared,
+            voices: Arc::new(voices),
+            worker,
+            edits: Mutex::new(None),
 
-        if error {
-            return None;
-        }
+            reports: Mutex::new(reports),
+        };
 
-        let frequency = Arc::new(AtomicF32::new(FREQUENCY_TEMP * TAU));
+        Some(AudioSendControl::Oscillator((stream, control)))
+    }
+}
 
-        let time = Arc::new(AtomicF32::new(0.0)); // will be received from bevy instance;
+impl Oscillator {
+    /// sources of the wave scripts, `None` until all of them are loaded.
+    pub fn scripts(&self, lua_assets: &Res<Assets<LuaAsset>>) -> Option<Vec<WaveScript>> {
+        self.lua_handle
+            .iter()
+            .map(|handle| {
+                Some(WaveScript {
+                    path: handle
+                        .path()
+                        .map_or("lua_pulse".to_string(), |path| path.to_string()),
+                    source: lua_assets.get(handle.clone())?.script.clone(),
+                })
+            })
+            .collect()
+    }
+}
 
-        let control = OscillatorControl {
-            frequency: frequency.clone(),
+/// new lua instance with all wave scripts loaded, and the errors loading them.
+fn load_wave(scripts: &[WaveScript]) -> (Lua, Vec<Error>) {
+    let lua = init_instance();
 
-            time: time.clone(),
-        };
+    // `@` makes lua name the file in its errors.
+    let errors = scripts
+        .iter()
+        .filter_map(|script| try_load_fn(&lua, &format!("@{}", script.path), &script.source).err())
+        .collect();
 
-        let stream = OscillatorStream {
-            frequency,
+    (lua, errors)
+}
 
-            luas,
+/// renders a block of one runtime, `write` gets every clamped sample in order, each one once.
+/// A failing lua call writes silence for the rest of the block and returns the error.
+fn render(
+    runtime: &mut WaveRuntime,
+    block_size: usize,
+    sample_rate: f32,
+    t0: f64,
+    frequency: f32,
+    mut write: impl FnMut(usize, (f32, f32)),
+) -> Result<()> {
+    let interval = 1.0 / sample_rate as f64;
+
+    match runtime {
+        WaveRuntime::Native(program) => {
+            (0..block_size).for_each(|i| {
+                let t = t0 + interval * i as f64;
+                let (out0, out1) = program.next(t, frequency, interval);
+
+                write(i, (norm(out0), norm(out1)));
+            });
 
-            duration: [(); AUDIO_SIZE].map(|_| Duration::from_secs(0)),
-            duration_idx: 0,
-        };
-        Some(AudioSendControl::Oscillator((stream, control)))
+            Ok(())
+        }
+        WaveRuntime::Lua(lua) => {
+            let mut written = 0;
+
+            let res = call_lua(
+                lua,
+                block_size,
+                sample_rate,
+                t0,
+                frequency,
+                |i, (out0, out1)| {
+                    write(i, (norm(out0), norm(out1)));
+                    written = i + 1;
+                },
+            );
+
+            // `write` may accumulate, samples written before the error stay as they are.
+            if res.is_err() {
+                (written..block_size).for_each(|i| write(i, (0.0, 0.0)));
+            }
+
+            res
+        }
     }
 }
 
-fn call_lua(lua: &Box<Lua>, i: f32, frequency: f32, t: f32) -> Result<(f32, f32)> {
+/// runs a whole block in one call, `write` gets every sample of the block.
+fn call_lua(
+    lua: &Lua,
+    block_size: usize,
+    sample_rate: f32,
+    t0: f64,
+    frequency: f32,
+    mut write: impl FnMut(usize, (f32, f32)),
+) -> Result<()> {
     lua.context(|ctx| {
-        let function: Function = ctx.globals().get(OUT)?;
+        let globals = ctx.globals();
+        let function: Function = globals.get(OUT)?;
+
+        function.call::<_, ()>((block_size, sample_rate, t0, frequency))?;
 
-        let out = function.call::<_, (f32, f32)>((i, frequency, t));
+        let out_l: Table = globals.get(OUT_L)?;
+        let out_r: Table = globals.get(OUT_R)?;
 
-        if out.is_err() {
-            info!("OUT ERROR -> {:?}", out);
+        for i in 0..block_size {
+            write(i, (out_l.get(i + 1)?, out_r.get(i + 1)?));
         }
 
-        Ok(out?)
+        Ok(())
     })
 }
 
 fn norm(input: f32) -> f32 {
     match input {
-        x if x <= 1. && x >= -1. => x,
+        x if (-1. ..=1.).contains(&x) => x,
         x if x > 1. => 1.,
         x if x < -1. => -1.,
-        _ => panic!("should not get here"),
+        _ => 0., // NaN
     }
 }
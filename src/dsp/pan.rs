@@ -0,0 +1,109 @@
+use std::{
+    f32::consts::{FRAC_PI_4, SQRT_2},
+    sync::{atomic::Ordering, Arc},
+};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_GAIN: f32 = 1.0;
+pub const DEFAULT_PAN: f32 = 0.0;
+
+// per sample smoothing of gain changes.
+const SMOOTHING: f32 = 0.002;
+
+/// Gain and equal power pan.
+#[derive(Clone)]
+pub struct Pan;
+
+pub struct PanStream {
+    gain: Arc<AtomicF32>,
+    pan: Arc<AtomicF32>,
+
+    current: [f32; 2],
+}
+
+impl PanStream {
+    fn generate_samples(&mut self, ctx: GenContext) {
+        let gain = self.gain.load(Ordering::Relaxed).max(0.0);
+        let pan = self.pan.load(Ordering::Relaxed).clamp(-1.0, 1.0);
+
+        // scaled so the center keeps unity gain.
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        let target = [angle.cos() * SQRT_2 * gain, angle.sin() * SQRT_2 * gain];
+
+        (0..ctx.block_size()).for_each(|i| {
+            (0..2).for_each(|ch| {
+                self.current[ch] += (target[ch] - self.current[ch]) * SMOOTHING;
+
+                ctx.outputs
+                    .write(ctx.inputs.read(ch, i) * self.current[ch], ch, i);
+            });
+        });
+    }
+}
+
+impl Gen for PanStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        self.generate_samples(ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct PanControl {
+    gain: Arc<AtomicF32>,
+    pan: Arc<AtomicF32>,
+}
+
+impl PanControl {
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain, Ordering::Relaxed);
+    }
+
+    /// -1.0 is hard left, 1.0 hard right.
+    pub fn set_pan(&self, pan: f32) {
+        self.pan.store(pan, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Pan {
+    type Stream = PanStream;
+    type Control = PanControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let gain = Arc::new(AtomicF32::new(DEFAULT_GAIN));
+        let pan = Arc::new(AtomicF32::new(DEFAULT_PAN));
+
+        let control = PanControl {
+            gain: gain.clone(),
+            pan: pan.clone(),
+        };
+
+        let stream = PanStream {
+            gain,
+            pan,
+
+            current: [DEFAULT_GAIN; 2],
+        };
+
+        Some(AudioSendControl::Pan((stream, control)))
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+// weight of the newest block in the smoothed values.
+const SMOOTHING: f32 = 0.05;
+// seconds for the peak to fall to about a third, a block above it is the new peak.
+const PEAK_DECAY: f32 = 1.0;
+
+/// Processing load of one stream, written by the audio thread after every block.
+#[derive(Default)]
+pub struct DspLoad {
+    // smoothed, in seconds.
+    time: AtomicF32,
+    // smoothed share of the block budget, 1.0 is the whole callback.
+    share: AtomicF32,
+    // highest share of a single block, falling back over `PEAK_DECAY`.
+    peak: AtomicF32,
+    // blocks this stream alone took longer than the budget, not the xruns of the device.
+    over_budget: AtomicUsize,
+}
+
+impl DspLoad {
+    fn record(&self, time: f32, budget: f32) {
+        let share = time / budget;
+
+        let smooth = |value: &AtomicF32, new: f32| {
+            let old = value.load(Ordering::Relaxed);
+            value.store(old + (new - old) * SMOOTHING, Ordering::Relaxed);
+        };
+
+        smooth(&self.time, time);
+        smooth(&self.share, share);
+
+        // only the audio thread writes, the peak decays by the length of the block.
+        let peak = self.peak.load(Ordering::Relaxed) * (-budget / PEAK_DECAY).exp();
+        self.peak.store(peak.max(share), Ordering::Relaxed);
+
+        if share > 1.0 {
+            self.over_budget.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// seconds per block.
+    pub fn time(&self) -> f32 {
+        self.time.load(Ordering::Relaxed)
+    }
+
+    /// part of the block budget, above 1.0 the callback can not keep up.
+    pub fn share(&self) -> f32 {
+        self.share.load(Ordering::Relaxed)
+    }
+
+    /// highest share of the last second or so.
+    pub fn peak(&self) -> f32 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// blocks that took longer than the whole budget.
+    pub fn over_budget(&self) -> usize {
+        self.over_budget.load(Ordering::Relaxed)
+    }
+}
+
+/// Wraps every stream pushed onto the graph, timing its `process`.
+pub struct Profiled<G: Gen> {
+    inner: G,
+    load: Arc<DspLoad>,
+}
+
+impl<G: Gen> Profiled<G> {
+    pub fn new(inner: G) -> (Self, Arc<DspLoad>) {
+        let load = Arc::new(DspLoad::default());
+
+        (
+            Self {
+                inner,
+                load: load.clone(),
+            },
+            load,
+        )
+    }
+}
+
+impl<G: Gen> Gen for Profiled<G> {
+    fn process(&mut self, ctx: GenContext, resources: &mut Resources) -> GenState {
+        // seconds of audio in the block, the time the callback has for all streams.
+        let budget = ctx.block_size() as f32 / ctx.sample_rate;
+        let start = Instant::now();
+
+        let state = self.inner.process(ctx, resources);
+
+        self.load.record(start.elapsed().as_secs_f32(), budget);
+
+        state
+    }
+
+    fn num_inputs(&self) -> usize {
+        self.inner.num_inputs()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.inner.num_outputs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 10ms blocks.
+    const BUDGET: f32 = 0.01;
+
+    #[test]
+    fn peak_falls_back_after_a_slow_block() {
+        let load = DspLoad::default();
+
+        load.record(BUDGET * 0.8, BUDGET);
+        assert!((load.peak() - 0.8).abs() < 1e-6);
+
+        // a second of light blocks.
+        (0..100).for_each(|_| load.record(BUDGET * 0.1, BUDGET));
+        assert!(load.peak() < 0.4);
+        assert!(load.peak() >= 0.1);
+    }
+
+    #[test]
+    fn blocks_over_the_budget_are_counted() {
+        let load = DspLoad::default();
+
+        load.record(BUDGET * 0.5, BUDGET);
+        load.record(BUDGET * 1.5, BUDGET);
+        load.record(BUDGET * 2.0, BUDGET);
+
+        assert_eq!(load.over_budget(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+#[cfg(feature = "realtime-debug")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bevy::{
+    ecs::{component::Component, entity::Entity, system::Query},
+    log::warn,
+};
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use crate::components::nodes::{generic::GenericNode, types::ParentNode};
+
+/// Counts heap use of threads inside `Realtime::process`, every other call goes straight to the system.
+/// Only installed with the `realtime-debug` feature, release builds keep the system allocator.
+#[cfg(feature = "realtime-debug")]
+struct RealtimeAllocator;
+
+#[cfg(feature = "realtime-debug")]
+#[global_allocator]
+static ALLOCATOR: RealtimeAllocator = RealtimeAllocator;
+
+thread_local! {
+    // const initialized without a destructor, reading them never allocates.
+    static GUARDED: Cell<bool> = const { Cell::new(false) };
+    static VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "realtime-debug")]
+fn count_violation() {
+    let _ = GUARDED.try_with(|guarded| {
+        if guarded.get() {
+            let _ = VIOLATIONS.try_with(|violations| violations.set(violations.get() + 1));
+        }
+    });
+}
+
+#[cfg(feature = "realtime-debug")]
+unsafe impl GlobalAlloc for RealtimeAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_violation();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_violation();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_violation();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count_violation();
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Violations of one node, written by the audio thread.
+#[derive(Default)]
+pub struct RealtimeReport {
+    // heap calls made while processing.
+    allocations: AtomicUsize,
+    // blocks with at least one heap call.
+    blocks: AtomicUsize,
+}
+
+impl RealtimeReport {
+    pub fn allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks.load(Ordering::Relaxed)
+    }
+}
+
+/// Debug wrapper around a stream, counts the heap calls its `process` makes.
+/// Locks and thread pool hops can not be seen from here, streams avoid them by construction.
+pub struct Realtime<G: Gen> {
+    inner: G,
+    report: Arc<RealtimeReport>,
+}
+
+impl<G: Gen> Realtime<G> {
+    pub fn new(inner: G) -> (Self, Arc<RealtimeReport>) {
+        let report = Arc::new(RealtimeReport::default());
+
+        (
+            Self {
+                inner,
+                report: report.clone(),
+            },
+            report,
+        )
+    }
+}
+
+impl<G: Gen> Gen for Realtime<G> {
+    fn process(&mut self, ctx: GenContext, resources: &mut Resources) -> GenState {
+        VIOLATIONS.with(|violations| violations.set(0));
+        GUARDED.with(|guarded| guarded.set(true));
+
+        let state = self.inner.process(ctx, resources);
+
+        GUARDED.with(|guarded| guarded.set(false));
+        let violations = VIOLATIONS.with(|violations| violations.get());
+
+        if violations > 0 {
+            self.report
+                .allocations
+                .fetch_add(violations, Ordering::Relaxed);
+            self.report.blocks.fetch_add(1, Ordering::Relaxed);
+        }
+
+        state
+    }
+
+    fn num_inputs(&self) -> usize {
+        self.inner.num_inputs()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.inner.num_outputs()
+    }
+}
+
+/// Report of the stream a grid node built, only inserted with `--realtime-debug`.
+#[derive(Component)]
+pub struct RealtimeCheck {
+    pub report: Arc<RealtimeReport>,
+    // allocations already warned about.
+    reported: usize,
+}
+
+impl RealtimeCheck {
+    pub fn new(report: Arc<RealtimeReport>) -> Self {
+        Self {
+            report,
+            reported: 0,
+        }
+    }
+}
+
+/// Warns about every node that allocated on the audio thread since the last frame.
+pub fn report_realtime_violations(mut query: Query<(Entity, &mut RealtimeCheck, &GenericNode)>) {
+    query.iter_mut().for_each(|(entity, mut check, gnode)| {
+        let allocations = check.report.allocations();
+
+        if allocations > check.reported {
+            let node = gnode.get_node();
+
+            warn!(
+                "realtime violation: {} at {}, {} - {} heap calls in {} blocks ({:?})",
+                node.name.to_string(),
+                node.pos.x,
+                node.pos.y,
+                allocations - check.reported,
+                check.report.blocks(),
+                entity
+            );
+
+            check.reported = allocations;
+        }
+    });
+}
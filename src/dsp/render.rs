@@ -0,0 +1,531 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::{App, AppExit, Startup, Update},
+    asset::{AssetApp, AssetPlugin, AssetServer, Assets},
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        query::With,
+        system::{Commands, Local, NonSendMut, Query, Res, ResMut, Resource},
+    },
+    log::{error, info, warn},
+    MinimalPlugins,
+};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::components::{
+    audio::AudioGraph,
+    config::ConfigAsset,
+    grid::Grid,
+    lua::{LuaAsset, LuaLoader},
+    nodes::{
+        blueprints::{init_temp_blueprints, initialize_gen_node},
+        generic::{system::link_chain, GenericNode},
+        lua::init_lua,
+        native::NativeNode,
+        system::{construct_native_node_from_node_bp, instrument_dsp},
+        types::{AudioNode, NodeBP, NodeTrait, NodeVarient, NotSetup, Position},
+    },
+    sample::{SampleAsset, SampleLoader},
+    transport::Transport,
+};
+
+use super::{
+    audio_graph::{AudioOutput, AudioPlugin},
+    backend::BackendSettings,
+    mixer::{Mixer, MIXER_INPUTS},
+    ChainType, Dsp, TChain,
+};
+
+/// amount of blocks rendered every frame, way faster than realtime.
+pub const RENDER_BLOCKS_PER_FRAME: usize = 1024;
+
+// grid distance between the chains of a patch (x) and the nodes of a chain (y).
+const PATCH_SPACING: (i32, i32) = (4, 3);
+
+/// Settings for rendering a patch to a wav file, read from the command line.
+/// `pulsar --render out.wav --seconds 10 --bit-depth 24 --patch sine,lowpass,audio_out`
+#[derive(Resource, Clone, Debug)]
+pub struct RenderSettings {
+    pub path: PathBuf,
+    pub seconds: f32,
+    pub bit_depth: BitDepth,
+    /// one chain per `--patch`, node names separated by commas.
+    pub patch: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum BitDepth {
+    Int16,
+    #[default]
+    Int24,
+    Float32,
+}
+
+impl BitDepth {
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        match bits {
+            16 => Some(BitDepth::Int16),
+            24 => Some(BitDepth::Int24),
+            32 => Some(BitDepth::Float32),
+            _ => None,
+        }
+    }
+
+    fn spec(&self, sample_rate: u32) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            BitDepth::Int16 => (16, SampleFormat::Int),
+            BitDepth::Int24 => (24, SampleFormat::Int),
+            BitDepth::Float32 => (32, SampleFormat::Float),
+        };
+
+        WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut path = None;
+        let mut seconds = 10.0;
+        let mut bit_depth = BitDepth::default();
+        let mut patch = vec![];
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--render" => path = args.next().map(PathBuf::from),
+                "--seconds" => {
+                    seconds = args
+                        .next()
+                        .and_then(|s| s.parse::<f32>().ok())
+                        .unwrap_or(seconds)
+                }
+                "--bit-depth" => {
+                    bit_depth = args
+                        .next()
+                        .and_then(|s| s.parse::<u16>().ok())
+                        .and_then(BitDepth::from_bits)
+                        .unwrap_or(bit_depth)
+                }
+                "--patch" => patch.extend(args.next()),
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            path: path?,
+            seconds,
+            bit_depth,
+            patch,
+        })
+    }
+}
+
+/// Builds an app that renders the patch without a window, only assets, blueprints and audio are set up.
+pub fn render_app(render: RenderSettings, backend: BackendSettings, config: ConfigAsset) -> App {
+    let mut app = App::new();
+
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        AudioPlugin {
+            render: Some(render),
+            backend,
+        },
+    ))
+    .insert_resource(Transport::from_config(&config))
+    .insert_resource(config)
+    .init_asset::<LuaAsset>()
+    .init_asset_loader::<LuaLoader>()
+    .init_asset::<SampleAsset>()
+    .init_asset_loader::<SampleLoader>()
+    .add_systems(Startup, (init_temp_blueprints, setup_render_grid))
+    .add_systems(Update, initialize_gen_node);
+
+    app
+}
+
+/// The grid without its mesh, the nodes of the patch are placed on it.
+fn setup_render_grid(mut commands: Commands) {
+    commands.spawn(Grid::default());
+}
+
+/// Inserts the chains of the rendered patch once the blueprints are loaded.
+/// Nodes are built from their blueprints and placed on the grid, one column per chain.
+/// Every `mixer` of the patch is the same node, chains reaching it are sent into its next input.
+/// Exits when none of them reaches an audio output, the render would never start.
+pub fn insert_render_patch(
+    mut commands: Commands,
+    mut graph: ResMut<AudioGraph>,
+    mut g_query: Query<&mut Grid>,
+    blueprints: Query<&GenericNode, With<NodeBP>>,
+    pending: Query<(), (With<NodeBP>, With<NotSetup>)>,
+    asset_server: Res<AssetServer>,
+    lua_assets: Res<Assets<LuaAsset>>,
+    render: Res<RenderSettings>,
+    mut inserted: Local<bool>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // blueprints are set up once their lua scripts are loaded.
+    if *inserted || blueprints.is_empty() || !pending.is_empty() {
+        return;
+    }
+    *inserted = true;
+
+    let mut grid = g_query.single_mut();
+    // the mixer entity, its chain and the next free input.
+    let mut mixer: Option<(Entity, usize, usize)> = None;
+
+    for (x, chain) in render.patch.iter().enumerate() {
+        let nodes = chain
+            .split(',')
+            .map(|name| {
+                blueprints
+                    .iter()
+                    .find_map(|node| match node {
+                        GenericNode::Native(node) if node.name().to_string() == name.trim() => {
+                            Some(node)
+                        }
+                        _ => None,
+                    })
+                    .ok_or(name)
+            })
+            .collect::<Result<Vec<&NativeNode>, &str>>();
+
+        let nodes = match nodes {
+            Ok(nodes) => nodes,
+            Err(name) => {
+                warn!("skipping chain {} - unknown node: {}", chain, name);
+                continue;
+            }
+        };
+
+        info!("inserting render chain: {}", chain);
+
+        let mut idx = None;
+
+        for (y, blueprint) in nodes.into_iter().enumerate() {
+            let pos = Position::new(x as i32 * PATCH_SPACING.0, -(y as i32) * PATCH_SPACING.1);
+
+            // every mixer of the patch is the same node, it carries on with its own chain.
+            if let NodeVarient::Mixer = blueprint.name() {
+                let (entity, mixer_idx, input) = match mixer.as_mut() {
+                    Some(mixer) => mixer,
+                    None => {
+                        let entity =
+                            spawn_patch_node(&mut commands, &mut grid, &lua_assets, blueprint, pos);
+
+                        let l = graph.get_chain_mut();
+                        l.push(TChain::vec(
+                            vec![TChain::dsp(Dsp::Mixer(Mixer), Some(entity))],
+                            Some(entity),
+                        ));
+                        let mixer_idx = l.len() - 1;
+
+                        commands.entity(entity).insert(AudioNode {
+                            idx: Some(mixer_idx),
+                        });
+                        graph.mark_dirty(mixer_idx);
+
+                        mixer.insert((entity, mixer_idx, 0))
+                    }
+                };
+
+                if let Some(idx) = idx {
+                    match link_chain(NodeVarient::Mixer, *entity, *input) {
+                        Some(link) if *input < MIXER_INPUTS => {
+                            push_link(&mut graph, idx, link);
+                            *input += 1;
+                        }
+                        _ => warn!("skipping bus send of {} - all mixer inputs are used", chain),
+                    }
+                }
+
+                idx = Some(*mixer_idx);
+                continue;
+            }
+
+            match idx {
+                None => {
+                    let dsp = match instrument_dsp(blueprint, &asset_server) {
+                        Some(dsp) => dsp,
+                        None => {
+                            warn!(
+                                "skipping chain {} - {} can not start a chain",
+                                chain,
+                                blueprint.name()
+                            );
+                            break;
+                        }
+                    };
+
+                    let entity =
+                        spawn_patch_node(&mut commands, &mut grid, &lua_assets, blueprint, pos);
+
+                    let l = graph.get_chain_mut();
+                    l.push(TChain::vec(
+                        vec![TChain::dsp(dsp, Some(entity))],
+                        Some(entity),
+                    ));
+                    idx = Some(l.len() - 1);
+
+                    commands.entity(entity).insert(AudioNode { idx });
+                }
+                Some(idx) => {
+                    let entity = commands.spawn_empty().id();
+
+                    match link_chain(blueprint.name(), entity, 0) {
+                        Some(link) => {
+                            push_link(&mut graph, idx, link);
+                            place_patch_node(
+                                &mut commands,
+                                &mut grid,
+                                &lua_assets,
+                                blueprint,
+                                pos,
+                                entity,
+                            );
+                        }
+                        None => {
+                            warn!(
+                                "skipping {} of chain {} - it can not be linked",
+                                blueprint.name(),
+                                chain
+                            );
+                            commands.entity(entity).despawn();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(idx) = idx {
+            graph.mark_dirty(idx);
+        }
+    }
+
+    if !graph.get_chain().iter().any(|chain| chain.is_terminated()) {
+        error!("nothing to render, no chain of the patch reaches an audio_out node.");
+        exit.send(AppExit);
+    }
+}
+
+fn push_link(graph: &mut AudioGraph, idx: usize, link: TChain) {
+    if let ChainType::ChainList(ref mut l) = graph.get_chain_mut()[idx].t.as_mut() {
+        l.push(link);
+    }
+}
+
+fn spawn_patch_node(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    lua_assets: &Res<Assets<LuaAsset>>,
+    blueprint: &NativeNode,
+    pos: Position,
+) -> Entity {
+    let entity = commands.spawn_empty().id();
+    place_patch_node(commands, grid, lua_assets, blueprint, pos, entity);
+
+    entity
+}
+
+/// builds the node from its blueprint, like placing it on the grid by hand.
+fn place_patch_node(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    lua_assets: &Res<Assets<LuaAsset>>,
+    blueprint: &NativeNode,
+    pos: Position,
+    entity: Entity,
+) {
+    let mut node = construct_native_node_from_node_bp(blueprint, pos);
+    init_lua(lua_assets, &mut node);
+
+    commands.entity(entity).insert(GenericNode::Native(node));
+    grid.add_to_grid(entity, pos.to_tuple());
+}
+
+/// WavSink - writes stereo frames into a wav file until the requested length is reached.
+/// Without a length it keeps writing until it is finalized.
+pub struct WavSink {
+    writer: Option<WavWriter<BufWriter<File>>>,
+    bit_depth: BitDepth,
+    frames_left: Option<usize>,
+}
+
+impl WavSink {
+    pub fn new(
+        path: &Path,
+        bit_depth: BitDepth,
+        sample_rate: usize,
+        seconds: Option<f32>,
+    ) -> Result<Self, hound::Error> {
+        let writer = WavWriter::create(path, bit_depth.spec(sample_rate as u32))?;
+
+        Ok(Self {
+            writer: Some(writer),
+            bit_depth,
+            frames_left: seconds.map(|seconds| (seconds * sample_rate as f32) as usize),
+        })
+    }
+
+    pub fn write(&mut self, left: f32, right: f32) -> Result<(), hound::Error> {
+        if self.is_done() {
+            return Ok(());
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            for sample in [left, right] {
+                let sample = sample.clamp(-1.0, 1.0);
+
+                match self.bit_depth {
+                    BitDepth::Int16 => writer.write_sample((sample * i16::MAX as f32) as i16)?,
+                    BitDepth::Int24 => writer.write_sample((sample * 8388607.0) as i32)?,
+                    BitDepth::Float32 => writer.write_sample(sample)?,
+                }
+            }
+        }
+
+        if let Some(frames_left) = self.frames_left.as_mut() {
+            *frames_left -= 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frames_left == Some(0)
+    }
+
+    pub fn finalize(&mut self) -> Result<(), hound::Error> {
+        match self.writer.take() {
+            Some(writer) => writer.finalize(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Renders the patch as fast as possible once its chains are built and one reaches an audio output.
+/// Exits the app when the requested length is written.
+pub fn render_offline(
+    graph: Res<AudioGraph>,
+    mut audio_output: NonSendMut<AudioOutput>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // waits for the assets of every chain, rendering starts with all of them playing.
+    if !graph.is_built() || !graph.get_chain().iter().any(|chain| chain.is_terminated()) {
+        return;
+    }
+
+    let backend = match audio_output.get_offline_mut() {
+        Some(backend) => backend,
+        None => return,
+    };
+
+    for _ in 0..RENDER_BLOCKS_PER_FRAME {
+        if let Err(e) = backend.process_block() {
+            error!("failed to write render - {}", e);
+            exit.send(AppExit);
+            return;
+        }
+
+        if let Some(sink) = backend.get_sink_mut() {
+            if sink.is_done() {
+                match sink.finalize() {
+                    Ok(_) => info!("render finished."),
+                    Err(e) => error!("failed to finalize render - {}", e),
+                }
+
+                exit.send(AppExit);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use bevy::ecs::event::Events;
+
+    use super::*;
+    use crate::dsp::{
+        audio_graph::AudioControl, backend::OFFLINE_SAMPLE_RATE, oscillators::Oscillator,
+    };
+
+    fn run(render: RenderSettings) -> App {
+        let config =
+            toml::from_str::<ConfigAsset>(&fs::read_to_string("assets/config.toml").unwrap())
+                .unwrap();
+
+        let mut app = render_app(render, BackendSettings::default(), config);
+        app.finish();
+        app.cleanup();
+
+        // blueprints and waves load on the io pool.
+        for _ in 0..10000 {
+            app.update();
+
+            if !app.world.resource::<Events<AppExit>>().is_empty() {
+                return app;
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        panic!("render did not finish.");
+    }
+
+    #[test]
+    fn renders_patch_headless() {
+        let path = std::env::temp_dir().join("pulsar_render_test.wav");
+
+        let mut app = run(RenderSettings {
+            path: path.clone(),
+            seconds: 0.1,
+            bit_depth: BitDepth::Int16,
+            patch: vec!["lua_pulse,lowpass,audio_out".to_string()],
+        });
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(
+            reader.spec(),
+            WavSpec {
+                channels: 2,
+                sample_rate: OFFLINE_SAMPLE_RATE as u32,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            }
+        );
+        assert_eq!(reader.duration(), OFFLINE_SAMPLE_RATE as u32 / 10);
+
+        // the lua pulse was built from its blueprint once its wave loaded.
+        let mut oscillators = app
+            .world
+            .query::<(&GenericNode, &AudioControl<Oscillator>)>();
+        assert_eq!(oscillators.iter(&app.world).count(), 1);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_render_folder_exits() {
+        run(RenderSettings {
+            path: std::env::temp_dir().join("pulsar_missing").join("out.wav"),
+            seconds: 0.1,
+            bit_depth: BitDepth::Int16,
+            patch: vec!["sine,audio_out".to_string()],
+        });
+    }
+}
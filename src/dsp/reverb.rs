@@ -0,0 +1,244 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use atomic_float::AtomicF32;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    delay::MAX_SAMPLE_RATE,
+    AudioSendControl,
+};
+
+pub const DEFAULT_ROOM: f32 = 0.8;
+pub const DEFAULT_DAMPING: f32 = 0.5;
+pub const DEFAULT_MIX: f32 = 0.3;
+
+// freeverb tunings, in samples at 44.1kHz.
+const TUNING_SAMPLE_RATE: f32 = 44100.0;
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_ROOM: f32 = 0.28;
+const OFFSET_ROOM: f32 = 0.7;
+const SCALE_DAMPING: f32 = 0.4;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+#[derive(Clone)]
+pub struct Reverb;
+
+/// delay line with room for the highest supported sample rate.
+struct Line {
+    buffer: Vec<f32>,
+    len: usize,
+    idx: usize,
+}
+
+impl Line {
+    fn new(tuning: usize) -> Self {
+        let capacity = scale(tuning, MAX_SAMPLE_RATE);
+
+        Self {
+            buffer: vec![0.0; capacity],
+            len: capacity,
+            idx: 0,
+        }
+    }
+
+    fn resize(&mut self, tuning: usize, sample_rate: f32) {
+        self.len = scale(tuning, sample_rate).min(self.buffer.len());
+        self.idx %= self.len;
+    }
+
+    fn advance(&mut self) {
+        self.idx = (self.idx + 1) % self.len;
+    }
+}
+
+struct Comb {
+    line: Line,
+    store: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let out = self.line.buffer[self.line.idx];
+
+        self.store = out * (1.0 - damping) + self.store * damping;
+        self.line.buffer[self.line.idx] = input + self.store * feedback;
+        self.line.advance();
+
+        out
+    }
+}
+
+struct Allpass {
+    line: Line,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.line.buffer[self.line.idx];
+
+        self.line.buffer[self.line.idx] = input + buffered * ALLPASS_FEEDBACK;
+        self.line.advance();
+
+        buffered - input
+    }
+}
+
+pub struct ReverbStream {
+    room: Arc<AtomicF32>,
+    damping: Arc<AtomicF32>,
+    mix: Arc<AtomicF32>,
+
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+
+    // lines are rescaled when the sample rate is first known.
+    sample_rate: f32,
+}
+
+impl ReverbStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        if sample_rate != self.sample_rate {
+            self.resize(sample_rate);
+        }
+
+        let feedback = self.room.load(Ordering::Relaxed).clamp(0.0, 1.0) * SCALE_ROOM + OFFSET_ROOM;
+        let damping = self.damping.load(Ordering::Relaxed).clamp(0.0, 1.0) * SCALE_DAMPING;
+        let mix = self.mix.load(Ordering::Relaxed).clamp(0.0, 1.0);
+
+        (0..ctx.block_size()).for_each(|i| {
+            let dry = [ctx.inputs.read(0, i), ctx.inputs.read(1, i)];
+            let input = (dry[0] + dry[1]) * FIXED_GAIN;
+
+            (0..2).for_each(|ch| {
+                let mut wet = self.combs[ch]
+                    .iter_mut()
+                    .map(|comb| comb.process(input, feedback, damping))
+                    .sum::<f32>();
+
+                self.allpasses[ch]
+                    .iter_mut()
+                    .for_each(|allpass| wet = allpass.process(wet));
+
+                ctx.outputs.write(dry[ch] * (1.0 - mix) + wet * mix, ch, i);
+            });
+        });
+    }
+
+    fn resize(&mut self, sample_rate: f32) {
+        (0..2).for_each(|ch| {
+            let spread = ch * STEREO_SPREAD;
+
+            self.combs[ch]
+                .iter_mut()
+                .zip(COMB_TUNING)
+                .for_each(|(comb, tuning)| comb.line.resize(tuning + spread, sample_rate));
+            self.allpasses[ch]
+                .iter_mut()
+                .zip(ALLPASS_TUNING)
+                .for_each(|(allpass, tuning)| allpass.line.resize(tuning + spread, sample_rate));
+        });
+
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl Gen for ReverbStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct ReverbControl {
+    room: Arc<AtomicF32>,
+    damping: Arc<AtomicF32>,
+    mix: Arc<AtomicF32>,
+}
+
+impl ReverbControl {
+    /// size of the room, 0.0 to 1.0.
+    pub fn set_room(&self, room: f32) {
+        self.room.store(room, Ordering::Relaxed);
+    }
+
+    /// high frequency loss of the tail, 0.0 to 1.0.
+    pub fn set_damping(&self, damping: f32) {
+        self.damping.store(damping, Ordering::Relaxed);
+    }
+
+    /// 0.0 is dry, 1.0 only the tail.
+    pub fn set_mix(&self, mix: f32) {
+        self.mix.store(mix, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Reverb {
+    type Stream = ReverbStream;
+    type Control = ReverbControl;
+
+    fn to_stream(&mut self, _assets: &DspAssets) -> Option<AudioSendControl> {
+        let room = Arc::new(AtomicF32::new(DEFAULT_ROOM));
+        let damping = Arc::new(AtomicF32::new(DEFAULT_DAMPING));
+        let mix = Arc::new(AtomicF32::new(DEFAULT_MIX));
+
+        let control = ReverbControl {
+            room: room.clone(),
+            damping: damping.clone(),
+            mix: mix.clone(),
+        };
+
+        let combs = [0, STEREO_SPREAD].map(|spread| {
+            COMB_TUNING
+                .iter()
+                .map(|tuning| Comb {
+                    line: Line::new(tuning + spread),
+                    store: 0.0,
+                })
+                .collect()
+        });
+        let allpasses = [0, STEREO_SPREAD].map(|spread| {
+            ALLPASS_TUNING
+                .iter()
+                .map(|tuning| Allpass {
+                    line: Line::new(tuning + spread),
+                })
+                .collect()
+        });
+
+        let stream = ReverbStream {
+            room,
+            damping,
+            mix,
+
+            combs,
+            allpasses,
+
+            sample_rate: 0.0,
+        };
+
+        Some(AudioSendControl::Reverb((stream, control)))
+    }
+}
+
+fn scale(tuning: usize, sample_rate: f32) -> usize {
+    ((tuning as f32 * sample_rate / TUNING_SAMPLE_RATE) as usize).max(1)
+}
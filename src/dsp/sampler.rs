@@ -0,0 +1,242 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use atomic_float::AtomicF32;
+use bevy::asset::Handle;
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+
+use crate::components::sample::SampleAsset;
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_START: f32 = 0.0;
+pub const DEFAULT_END: f32 = 1.0;
+pub const DEFAULT_PITCH: f32 = 0.0;
+
+/// played by sampler blueprints that do not set `node.asset`.
+pub const DEFAULT_SAMPLE: &str = "samples/kick.wav";
+
+/// What happens when playback reaches the end of the range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopMode {
+    Off,
+    Forward,
+    PingPong,
+}
+
+impl LoopMode {
+    /// slot values are rounded, 0 - off, 1 - forward, 2 - ping pong.
+    pub fn from_f32(value: f32) -> Self {
+        match value.round() as i32 {
+            1 => LoopMode::Forward,
+            2 => LoopMode::PingPong,
+            _ => LoopMode::Off,
+        }
+    }
+
+    fn from_usize(value: usize) -> Self {
+        match value {
+            1 => LoopMode::Forward,
+            2 => LoopMode::PingPong,
+            _ => LoopMode::Off,
+        }
+    }
+
+    fn to_usize(self) -> usize {
+        match self {
+            LoopMode::Off => 0,
+            LoopMode::Forward => 1,
+            LoopMode::PingPong => 2,
+        }
+    }
+}
+
+/// Plays a sample asset from the start of its range on every trigger.
+#[derive(Clone)]
+pub struct Sampler {
+    pub handle: Handle<SampleAsset>,
+}
+
+pub struct SamplerStream {
+    frames: Arc<Vec<(f32, f32)>>,
+    sample_rate: f32,
+
+    start: Arc<AtomicF32>,
+    end: Arc<AtomicF32>,
+    pitch: Arc<AtomicF32>,
+    loop_mode: Arc<AtomicUsize>,
+    triggers: Arc<AtomicUsize>,
+
+    last_trigger: usize,
+
+    playing: bool,
+    // frame position, fractional when pitched.
+    position: f64,
+    direction: f64,
+}
+
+impl SamplerStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let len = self.frames.len();
+        let last = len.saturating_sub(1) as f64;
+
+        // an end before the start plays the range backwards.
+        let start = self.start.load(Ordering::Relaxed).clamp(0.0, 1.0) as f64 * last;
+        let end = self.end.load(Ordering::Relaxed).clamp(0.0, 1.0) as f64 * last;
+        let (low, high) = (start.min(end), start.max(end));
+
+        let triggers = self.triggers.load(Ordering::Relaxed);
+        if triggers != self.last_trigger {
+            self.last_trigger = triggers;
+
+            self.playing = high - low >= 1.0;
+            self.position = start;
+            self.direction = if start <= end { 1.0 } else { -1.0 };
+        }
+
+        let rate = 2.0_f64.powf(self.pitch.load(Ordering::Relaxed) as f64 / 12.0)
+            * (self.sample_rate / sample_rate) as f64;
+        let loop_mode = LoopMode::from_usize(self.loop_mode.load(Ordering::Relaxed));
+
+        (0..ctx.block_size()).for_each(|i| {
+            if !self.playing {
+                ctx.outputs.write(0.0, 0, i);
+                ctx.outputs.write(0.0, 1, i);
+                return;
+            }
+
+            let idx = self.position.floor() as usize;
+            let frac = self.position.fract() as f32;
+
+            let a = self.frames[idx.min(len - 1)];
+            let b = self.frames[(idx + 1).min(len - 1)];
+
+            ctx.outputs.write(a.0 + (b.0 - a.0) * frac, 0, i);
+            ctx.outputs.write(a.1 + (b.1 - a.1) * frac, 1, i);
+
+            self.position += rate * self.direction;
+
+            if self.position >= low && self.position <= high {
+                return;
+            }
+
+            match loop_mode {
+                LoopMode::Off => self.playing = false,
+                LoopMode::Forward => {
+                    self.position = match self.direction > 0.0 {
+                        true => low + (self.position - high),
+                        false => high - (low - self.position),
+                    }
+                }
+                LoopMode::PingPong => {
+                    self.position = match self.position > high {
+                        true => high - (self.position - high),
+                        false => low + (low - self.position),
+                    };
+                    self.direction = -self.direction;
+                }
+            }
+
+            // pitched far up the overshoot can be longer than the range.
+            self.position = self.position.clamp(low, high);
+        });
+    }
+}
+
+impl Gen for SamplerStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct SamplerControl {
+    start: Arc<AtomicF32>,
+    end: Arc<AtomicF32>,
+    pitch: Arc<AtomicF32>,
+    loop_mode: Arc<AtomicUsize>,
+    triggers: Arc<AtomicUsize>,
+}
+
+impl SamplerControl {
+    /// plays the sample from the start of the range.
+    pub fn trigger(&self) {
+        self.triggers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// range to play, 0.0 to 1.0 of the sample length.
+    pub fn set_range(&self, start: f32, end: f32) {
+        self.start.store(start, Ordering::Relaxed);
+        self.end.store(end, Ordering::Relaxed);
+    }
+
+    /// pitch in semitones, 0.0 plays at the recorded speed.
+    pub fn set_pitch(&self, semitones: f32) {
+        self.pitch.store(semitones, Ordering::Relaxed);
+    }
+
+    pub fn set_loop(&self, mode: LoopMode) {
+        self.loop_mode.store(mode.to_usize(), Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Sampler {
+    type Stream = SamplerStream;
+    type Control = SamplerControl;
+
+    fn to_stream(&mut self, assets: &DspAssets) -> Option<AudioSendControl> {
+        let sample = assets.samples.get(&self.handle)?;
+
+        let start = Arc::new(AtomicF32::new(DEFAULT_START));
+        let end = Arc::new(AtomicF32::new(DEFAULT_END));
+        let pitch = Arc::new(AtomicF32::new(DEFAULT_PITCH));
+        let loop_mode = Arc::new(AtomicUsize::new(LoopMode::Off.to_usize()));
+        let triggers = Arc::new(AtomicUsize::new(0));
+
+        let control = SamplerControl {
+            start: start.clone(),
+            end: end.clone(),
+            pitch: pitch.clone(),
+            loop_mode: loop_mode.clone(),
+            triggers: triggers.clone(),
+        };
+
+        let stream = SamplerStream {
+            frames: sample.frames.clone(),
+            sample_rate: sample.sample_rate,
+
+            start,
+            end,
+            pitch,
+            loop_mode,
+            triggers,
+
+            last_trigger: 0,
+
+            playing: false,
+            position: 0.0,
+            direction: 1.0,
+        };
+
+        Some(AudioSendControl::Sampler((stream, control)))
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    f32::consts::TAU,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use atomic_float::AtomicF32;
+
+use super::envelope::Stage;
+
+/// voices built for every instrument, the voice count slot picks how many are used.
+pub const MAX_VOICES: usize = 8;
+
+pub const DEFAULT_VOICES: usize = 0;
+pub const DEFAULT_ATTACK: f32 = 0.005;
+pub const DEFAULT_RELEASE: f32 = 0.4;
+
+// shortest segment, avoids clicks and division by zero.
+const MIN_TIME: f32 = 0.001;
+
+// key of a voice playing a one shot, note offs never match it.
+const NO_KEY: usize = usize::MAX;
+
+/// Which voice gives way when a note arrives and all voices are playing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StealMode {
+    Oldest,
+    Quietest,
+}
+
+impl StealMode {
+    /// slot values are rounded, 0 - oldest, 1 - quietest.
+    pub fn from_f32(value: f32) -> Self {
+        match value.round() as i32 {
+            1 => StealMode::Quietest,
+            _ => StealMode::Oldest,
+        }
+    }
+
+    fn from_usize(value: usize) -> Self {
+        match value {
+            1 => StealMode::Quietest,
+            _ => StealMode::Oldest,
+        }
+    }
+
+    fn to_usize(self) -> usize {
+        match self {
+            StealMode::Oldest => 0,
+            StealMode::Quietest => 1,
+        }
+    }
+}
+
+/// State of one voice shared between the control and the stream.
+#[derive(Default)]
+struct VoiceShared {
+    // radians per second.
+    frequency: AtomicF32,
+    gate: AtomicBool,
+    triggers: AtomicUsize,
+    // midi key holding the gate open.
+    key: AtomicUsize,
+
+    // written back by the stream.
+    handled: AtomicUsize,
+    level: AtomicF32,
+
+    // note on order, the oldest voice has the lowest number.
+    started: AtomicU64,
+}
+
+/// Amplitude of a voice on the audio thread, an attack / release envelope following the gate.
+/// A trigger with a closed gate plays a one shot, attack straight into release.
+pub struct VoiceEnvelope {
+    shared: Arc<VoiceShared>,
+    // seconds, shared by every voice of the instrument.
+    attack: Arc<AtomicF32>,
+    release: Arc<AtomicF32>,
+
+    last_trigger: usize,
+    last_gate: bool,
+
+    stage: Stage,
+    level: f32,
+    attack_step: f32,
+    release_step: f32,
+}
+
+impl VoiceEnvelope {
+    /// reads the voice state, called once per block before `next`.
+    pub fn update(&mut self, sample_rate: f32) {
+        let triggers = self.shared.triggers.load(Ordering::Relaxed);
+        let gate = self.shared.gate.load(Ordering::Relaxed);
+
+        // a stolen voice restarts the attack from its current level.
+        if triggers != self.last_trigger {
+            self.stage = Stage::Attack;
+        }
+
+        self.last_trigger = triggers;
+        self.last_gate = gate;
+        self.shared.handled.store(triggers, Ordering::Relaxed);
+
+        self.attack_step = 1.0 / (self.attack.load(Ordering::Relaxed).max(MIN_TIME) * sample_rate);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Idle
+    }
+
+    /// radians per second.
+    pub fn frequency(&self) -> f32 {
+        self.shared.frequency.load(Ordering::Relaxed)
+    }
+
+    /// level of the next sample.
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Attack => {
+                self.level += self.attack_step;
+
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+
+                    match self.last_gate {
+                        true => self.stage = Stage::Sustain,
+                        false => self.start_release(sample_rate),
+                    }
+                }
+            }
+            Stage::Decay | Stage::Sustain => {
+                if !self.last_gate {
+                    self.start_release(sample_rate);
+                }
+            }
+            Stage::Release => {
+                self.level -= self.release_step;
+
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+
+        self.level
+    }
+
+    /// publishes the level for voice stealing, called once per block after `next`.
+    pub fn finish(&self) {
+        self.shared.level.store(self.level, Ordering::Relaxed);
+    }
+
+    fn start_release(&mut self, sample_rate: f32) {
+        self.release_step =
+            self.level / (self.release.load(Ordering::Relaxed).max(MIN_TIME) * sample_rate);
+        self.stage = Stage::Release;
+    }
+}
+
+/// Allocates notes to the voices of an instrument.
+pub struct VoicesControl {
+    voices: Vec<Arc<VoiceShared>>,
+    count: Arc<AtomicUsize>,
+    steal: AtomicUsize,
+    order: AtomicU64,
+
+    attack: Arc<AtomicF32>,
+    release: Arc<AtomicF32>,
+}
+
+impl VoicesControl {
+    /// control and stream envelopes for `MAX_VOICES` voices.
+    pub fn new() -> (Self, Vec<VoiceEnvelope>) {
+        let voices = (0..MAX_VOICES)
+            .map(|_| Arc::new(VoiceShared::default()))
+            .collect::<Vec<Arc<VoiceShared>>>();
+
+        let control = VoicesControl {
+            voices,
+            count: Arc::new(AtomicUsize::new(DEFAULT_VOICES)),
+            steal: AtomicUsize::new(StealMode::Oldest.to_usize()),
+            order: AtomicU64::new(0),
+
+            attack: Arc::new(AtomicF32::new(DEFAULT_ATTACK)),
+            release: Arc::new(AtomicF32::new(DEFAULT_RELEASE)),
+        };
+
+        let envelopes = control.envelopes();
+
+        (control, envelopes)
+    }
+
+    /// stream envelopes following the voices, a rebuilt stream picks up the notes that are playing.
+    pub fn envelopes(&self) -> Vec<VoiceEnvelope> {
+        self.voices
+            .iter()
+            .map(|shared| {
+                let level = shared.level.load(Ordering::Relaxed);
+
+                VoiceEnvelope {
+                    shared: shared.clone(),
+                    attack: self.attack.clone(),
+                    release: self.release.clone(),
+                    last_trigger: shared.triggers.load(Ordering::Relaxed),
+                    last_gate: shared.gate.load(Ordering::Relaxed),
+                    // a sounding voice holds its level, the closed gate releases it.
+                    stage: match level > 0.0 {
+                        true => Stage::Sustain,
+                        false => Stage::Idle,
+                    },
+                    level,
+                    attack_step: 0.0,
+                    release_step: 0.0,
+                }
+            })
+            .collect()
+    }
+
+    /// shared with the stream, 0 voices plays the instrument free running.
+    pub fn count_handle(&self) -> Arc<AtomicUsize> {
+        self.count.clone()
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn set_count(&self, count: usize) {
+        self.count.store(count.min(MAX_VOICES), Ordering::Relaxed);
+    }
+
+    pub fn set_steal(&self, mode: StealMode) {
+        self.steal.store(mode.to_usize(), Ordering::Relaxed);
+    }
+
+    /// attack and release of every voice in seconds.
+    pub fn set_envelope(&self, attack: f32, release: f32) {
+        self.attack.store(attack, Ordering::Relaxed);
+        self.release.store(release, Ordering::Relaxed);
+    }
+
+    /// plays a one shot on a free or stolen voice, returns the voice.
+    pub fn trigger(&self, frequency_hz: f32) -> Option<usize> {
+        self.start(frequency_hz, None)
+    }
+
+    /// holds a voice until the note off of `key`, a key that is still held is played again.
+    pub fn note_on(&self, key: u8, frequency_hz: f32) -> Option<usize> {
+        self.note_off(key);
+        self.start(frequency_hz, Some(key))
+    }
+
+    /// releases the voices held by `key`.
+    pub fn note_off(&self, key: u8) {
+        self.voices
+            .iter()
+            .filter(|shared| shared.key.load(Ordering::Relaxed) == key as usize)
+            .for_each(|shared| shared.gate.store(false, Ordering::Relaxed));
+    }
+
+    // without a key the gate stays closed and the voice plays a one shot.
+    fn start(&self, frequency_hz: f32, key: Option<u8>) -> Option<usize> {
+        let voice = self.allocate()?;
+        let shared = &self.voices[voice];
+
+        shared
+            .frequency
+            .store(frequency_hz * TAU, Ordering::Relaxed);
+        shared
+            .key
+            .store(key.map_or(NO_KEY, usize::from), Ordering::Relaxed);
+        shared.gate.store(key.is_some(), Ordering::Relaxed);
+        shared.started.store(
+            self.order.fetch_add(1, Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        shared.triggers.fetch_add(1, Ordering::Relaxed);
+
+        Some(voice)
+    }
+
+    /// a silent voice if there is one, otherwise the one the steal mode picks.
+    fn allocate(&self) -> Option<usize> {
+        let voices = &self.voices[..self.count()];
+
+        // a voice triggered since the last block is busy even though it is still silent.
+        let free = voices.iter().position(|shared| {
+            !shared.gate.load(Ordering::Relaxed)
+                && shared.level.load(Ordering::Relaxed) == 0.0
+                && shared.handled.load(Ordering::Relaxed) == shared.triggers.load(Ordering::Relaxed)
+        });
+
+        free.or_else(|| {
+            let voices = voices.iter().enumerate();
+
+            match StealMode::from_usize(self.steal.load(Ordering::Relaxed)) {
+                StealMode::Oldest => voices
+                    .min_by_key(|(_, shared)| shared.started.load(Ordering::Relaxed))
+                    .map(|(i, _)| i),
+                StealMode::Quietest => voices
+                    .min_by(|(_, a), (_, b)| {
+                        a.level
+                            .load(Ordering::Relaxed)
+                            .total_cmp(&b.level.load(Ordering::Relaxed))
+                    })
+                    .map(|(i, _)| i),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    // one block of the stream, every envelope runs `samples` samples.
+    fn run(envelopes: &mut [VoiceEnvelope], samples: usize) {
+        for envelope in envelopes.iter_mut() {
+            envelope.update(SAMPLE_RATE);
+
+            for _ in 0..samples {
+                envelope.next(SAMPLE_RATE);
+            }
+
+            envelope.finish();
+        }
+    }
+
+    #[test]
+    fn notes_take_free_voices_first() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(3);
+
+        assert_eq!(control.trigger(220.0), Some(0));
+        // not handled by the stream yet, still busy.
+        assert_eq!(control.trigger(330.0), Some(1));
+
+        run(&mut envelopes, 1);
+        assert_eq!(control.trigger(440.0), Some(2));
+        assert_eq!(envelopes[1].frequency(), 330.0 * TAU);
+    }
+
+    #[test]
+    fn no_voices_no_notes() {
+        let (control, _) = VoicesControl::new();
+
+        assert_eq!(control.trigger(220.0), None);
+
+        control.set_count(MAX_VOICES + 4);
+        assert_eq!(control.count(), MAX_VOICES);
+    }
+
+    #[test]
+    fn oldest_voice_is_stolen() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(2);
+
+        control.trigger(220.0);
+        control.trigger(330.0);
+        run(&mut envelopes, 1);
+
+        assert_eq!(control.trigger(440.0), Some(0));
+        assert_eq!(control.trigger(550.0), Some(1));
+    }
+
+    #[test]
+    fn quietest_voice_is_stolen() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(2);
+        control.set_steal(StealMode::Quietest);
+
+        control.trigger(220.0);
+        run(&mut envelopes, 5);
+        control.trigger(330.0);
+        run(&mut envelopes[1..], 1);
+
+        // the second voice is still early in its attack.
+        assert_eq!(control.trigger(440.0), Some(1));
+    }
+
+    #[test]
+    fn one_shots_release_back_to_idle() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(1);
+
+        control.trigger(220.0);
+        run(&mut envelopes, 1);
+        assert!(envelopes[0].is_active());
+
+        let attack = (DEFAULT_ATTACK * SAMPLE_RATE) as usize;
+        let release = (DEFAULT_RELEASE * SAMPLE_RATE) as usize;
+        run(&mut envelopes, attack + release + 2);
+
+        assert!(!envelopes[0].is_active());
+        assert_eq!(control.trigger(330.0), Some(0));
+    }
+
+    #[test]
+    fn held_notes_sustain_until_their_note_off() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(2);
+        control.set_envelope(0.001, 0.001);
+
+        assert_eq!(control.note_on(60, 261.6), Some(0));
+        assert_eq!(control.note_on(64, 329.6), Some(1));
+        run(&mut envelopes, 100);
+        assert!(envelopes.iter().take(2).all(|envelope| envelope.is_active()));
+
+        control.note_off(60);
+        run(&mut envelopes, 100);
+        assert!(!envelopes[0].is_active());
+        assert!(envelopes[1].is_active());
+
+        // the released voice is free again, the held one is not.
+        assert_eq!(control.note_on(67, 392.0), Some(0));
+    }
+
+    #[test]
+    fn envelope_times_follow_the_control() {
+        let (control, mut envelopes) = VoicesControl::new();
+        control.set_count(1);
+        control.set_envelope(0.01, 0.02);
+
+        control.trigger(220.0);
+        run(&mut envelopes, 10 + 20 + 2);
+        assert!(!envelopes[0].is_active());
+
+        control.set_envelope(0.01, 1.0);
+        control.trigger(220.0);
+        run(&mut envelopes, 10 + 20 + 2);
+        assert!(envelopes[0].is_active());
+    }
+}
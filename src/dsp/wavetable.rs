@@ -0,0 +1,263 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use atomic_float::AtomicF32;
+use bevy::{
+    asset::{AssetId, AssetServer, Handle},
+    log::warn,
+};
+use knyst::{
+    gen::Gen,
+    prelude::{GenContext, GenState},
+    Resources,
+};
+use rlua::{Lua, Table};
+
+use crate::{
+    components::{lua::LuaAsset, sample::SampleAsset},
+    lua::{init_instance, try_load_fn},
+};
+
+use super::{
+    audio_graph::{DspAssets, Streamable},
+    AudioSendControl,
+};
+
+pub const DEFAULT_FREQUENCY: f32 = 110.0;
+pub const DEFAULT_AMPLITUDE: f32 = 0.5;
+pub const DEFAULT_POSITION: f32 = 0.0;
+
+/// played by wavetable blueprints that do not set `node.asset`.
+pub const DEFAULT_WAVETABLE: &str = "lua/nodes/instrument/wavetable/table.lua";
+
+/// samples per frame in wav tables, the common size of single cycle packs.
+pub const WAV_FRAME_SIZE: usize = 2048;
+/// every frame is resampled to this size so frames can be blended sample by sample.
+const TABLE_SIZE: usize = 2048;
+
+// global a table script has to fill, a list of frames that are lists of samples.
+const WAVETABLE: &str = "WAVETABLE";
+
+// per sample smoothing of the position, avoids steps when scanning the table.
+const POSITION_SMOOTHING: f32 = 0.001;
+
+/// Where the frames of a wavetable come from.
+#[derive(Clone)]
+pub enum WavetableSource {
+    /// frames of `WAV_FRAME_SIZE` samples, left channel only.
+    Sample(Handle<SampleAsset>),
+    /// a script setting `WAVETABLE`, run once when the chain is built.
+    Lua(Handle<LuaAsset>),
+}
+
+impl WavetableSource {
+    /// tables are lua scripts or audio files, picked by extension.
+    pub fn load(asset_server: &AssetServer, path: String) -> Self {
+        match path.ends_with(".lua") {
+            true => WavetableSource::Lua(asset_server.load(path)),
+            false => WavetableSource::Sample(asset_server.load(path)),
+        }
+    }
+
+    pub fn uses_lua(&self, id: AssetId<LuaAsset>) -> bool {
+        matches!(self, WavetableSource::Lua(handle) if handle.id() == id)
+    }
+
+    pub fn uses_sample(&self, id: AssetId<SampleAsset>) -> bool {
+        matches!(self, WavetableSource::Sample(handle) if handle.id() == id)
+    }
+}
+
+/// Oscillator reading single cycle frames, the position blends between neighbouring frames.
+#[derive(Clone)]
+pub struct Wavetable {
+    pub source: WavetableSource,
+}
+
+pub struct WavetableStream {
+    frequency: Arc<AtomicF32>,
+    amplitude: Arc<AtomicF32>,
+    position: Arc<AtomicF32>,
+
+    frames: Vec<Vec<f32>>,
+
+    phase: f32,
+    current: f32,
+}
+
+impl WavetableStream {
+    fn generate_samples(&mut self, sample_rate: f32, ctx: GenContext) {
+        let increment = self.frequency.load(Ordering::Relaxed).max(0.0) / sample_rate;
+        let amplitude = self.amplitude.load(Ordering::Relaxed);
+        let target =
+            self.position.load(Ordering::Relaxed).clamp(0.0, 1.0) * (self.frames.len() - 1) as f32;
+
+        (0..ctx.block_size()).for_each(|i| {
+            self.current += (target - self.current) * POSITION_SMOOTHING;
+
+            let frame = self.current.floor() as usize;
+            let blend = self.current.fract();
+
+            let a = read(&self.frames[frame], self.phase);
+            let b = read(
+                &self.frames[(frame + 1).min(self.frames.len() - 1)],
+                self.phase,
+            );
+
+            let out = (a + (b - a) * blend) * amplitude;
+
+            ctx.outputs.write(out, 0, i);
+            ctx.outputs.write(out, 1, i);
+
+            self.phase = (self.phase + increment).fract();
+        });
+    }
+}
+
+impl Gen for WavetableStream {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let sample_rate = ctx.sample_rate;
+        self.generate_samples(sample_rate, ctx);
+
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+}
+
+pub struct WavetableControl {
+    frequency: Arc<AtomicF32>,
+    amplitude: Arc<AtomicF32>,
+    position: Arc<AtomicF32>,
+}
+
+impl WavetableControl {
+    pub fn set_frequency(&self, frequency_hz: f32) {
+        self.frequency.store(frequency_hz, Ordering::Relaxed);
+    }
+
+    pub fn set_amplitude(&self, amplitude: f32) {
+        self.amplitude.store(amplitude, Ordering::Relaxed);
+    }
+
+    /// 0.0 is the first frame, 1.0 the last one.
+    pub fn set_position(&self, position: f32) {
+        self.position.store(position, Ordering::Relaxed);
+    }
+}
+
+impl Streamable for Wavetable {
+    type Stream = WavetableStream;
+    type Control = WavetableControl;
+
+    fn to_stream(&mut self, assets: &DspAssets) -> Option<AudioSendControl> {
+        let frames = match &self.source {
+            WavetableSource::Sample(handle) => sample_frames(assets.samples.get(handle)?),
+            WavetableSource::Lua(handle) => lua_frames(assets.lua.get(handle)?),
+        };
+
+        let frames = frames
+            .iter()
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| resample(frame))
+            .collect::<Vec<Vec<f32>>>();
+
+        // a broken table still builds, it plays silence until it is fixed.
+        let frames = match frames.is_empty() {
+            true => vec![vec![0.0; TABLE_SIZE]],
+            false => frames,
+        };
+
+        let frequency = Arc::new(AtomicF32::new(DEFAULT_FREQUENCY));
+        let amplitude = Arc::new(AtomicF32::new(DEFAULT_AMPLITUDE));
+        let position = Arc::new(AtomicF32::new(DEFAULT_POSITION));
+
+        let control = WavetableControl {
+            frequency: frequency.clone(),
+            amplitude: amplitude.clone(),
+            position: position.clone(),
+        };
+
+        let stream = WavetableStream {
+            frequency,
+            amplitude,
+            position,
+
+            frames,
+
+            phase: 0.0,
+            current: 0.0,
+        };
+
+        Some(AudioSendControl::Wavetable((stream, control)))
+    }
+}
+
+/// splits the left channel into frames, a file shorter than a frame is a single one.
+fn sample_frames(sample: &SampleAsset) -> Vec<Vec<f32>> {
+    let samples = sample.frames.iter().map(|(l, _)| *l).collect::<Vec<f32>>();
+
+    if samples.len() < WAV_FRAME_SIZE {
+        return vec![samples];
+    }
+
+    let frames = samples.chunks_exact(WAV_FRAME_SIZE);
+
+    if !frames.remainder().is_empty() {
+        warn!(
+            "wavetable sample is not a whole number of {} sample frames, dropping the last {} samples",
+            WAV_FRAME_SIZE,
+            frames.remainder().len()
+        );
+    }
+
+    frames.map(|frame| frame.to_vec()).collect()
+}
+
+fn lua_frames(asset: &LuaAsset) -> Vec<Vec<f32>> {
+    let lua = init_instance();
+
+    // a script that does not load is reported, not the missing table it leaves behind.
+    match try_load_fn(&lua, "wavetable", &asset.script).and_then(|_| read_table(&lua)) {
+        Ok(frames) => frames,
+        Err(err) => {
+            warn!("cannot read wavetable - {}", err);
+            vec![]
+        }
+    }
+}
+
+fn read_table(lua: &Lua) -> rlua::Result<Vec<Vec<f32>>> {
+    lua.context(|ctx| {
+        let table: Table = ctx.globals().get(WAVETABLE)?;
+
+        table
+            .sequence_values::<Table>()
+            .map(|frame| frame?.sequence_values::<f32>().collect())
+            .collect()
+    })
+}
+
+/// linear resampling of a single cycle, wrapping around at the end.
+fn resample(frame: &[f32]) -> Vec<f32> {
+    (0..TABLE_SIZE)
+        .map(|i| read(frame, i as f32 / TABLE_SIZE as f32))
+        .collect()
+}
+
+/// reads a cycle at a phase between 0.0 and 1.0.
+fn read(frame: &[f32], phase: f32) -> f32 {
+    let pos = phase * frame.len() as f32;
+    let idx = pos.floor() as usize % frame.len();
+    let frac = pos.fract();
+
+    let a = frame[idx];
+    let b = frame[(idx + 1) % frame.len()];
+
+    a + (b - a) * frac
+}
@@ -1,9 +1,41 @@
-// TODO: egui stuff
+use bevy::ecs::system::Res;
+use bevy_egui::{egui, EguiContexts};
 
-// use bevy_egui::{egui, EguiContexts};
+use crate::components::audio::WaveErrors;
 
-// pub fn edit_oscilo_text(mut contexts: EguiContexts) {
-//     egui::Window::new("Hello").show(contexts.ctx_mut(), |ui| {
-//         ui.label("world");
-//     });
-// }
+// `util::RED`
+const ERROR_COLOR: egui::Color32 = egui::Color32::from_rgb(243, 139, 168);
+
+/// Lists the lua errors of playing waves at the bottom of the window.
+/// Hidden while there are none, a reloaded wave clears the errors of its node.
+pub fn show_wave_errors(mut contexts: EguiContexts, errors: Res<WaveErrors>) {
+    if errors.is_empty() {
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("wave_errors").show(contexts.ctx_mut(), |ui| {
+        ui.heading("lua errors");
+
+        errors.iter().for_each(|node_error| {
+            let error = &node_error.error;
+
+            let location = match (&error.file, error.line) {
+                (Some(file), Some(line)) => format!("{}:{}", file, line),
+                _ => "unknown".to_string(),
+            };
+            let repeated = match node_error.count {
+                1 => "".to_string(),
+                count => format!(" (x{})", count),
+            };
+
+            ui.horizontal(|ui| {
+                ui.monospace(format!(
+                    "{} at {}, {}",
+                    node_error.node, node_error.pos.x, node_error.pos.y
+                ));
+                ui.monospace(location);
+                ui.colored_label(ERROR_COLOR, format!("{}{}", error.message, repeated));
+            });
+        });
+    });
+}
@@ -51,7 +51,7 @@ impl ExtractComponent for InstanceMaterialData {
     fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self> {
         Some(InstanceMaterialData {
             data: item.data.clone(),
-            layer: item.layer.clone(),
+            layer: item.layer,
         })
     }
 }
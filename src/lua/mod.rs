@@ -24,7 +24,10 @@ pub fn load_globals(lua: &Lua) {
 }
 
 pub fn load_fn(lua: &Lua, name: &str, method_name: &str) {
-    lua.context(|lua_ctx| {
-        let _ = lua_ctx.load(method_name).set_name(name).unwrap().exec();
-    });
+    let _ = try_load_fn(lua, name, method_name);
+}
+
+/// like `load_fn`, returning the error of a script that does not compile or run.
+pub fn try_load_fn(lua: &Lua, name: &str, method_name: &str) -> rlua::Result<()> {
+    lua.context(|lua_ctx| lua_ctx.load(method_name).set_name(name)?.exec())
 }
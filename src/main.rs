@@ -1,3 +1,6 @@
+// bevy systems take their queries and resources as arguments.
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
 mod components;
 mod dsp;
 mod egui;
@@ -20,10 +23,10 @@ use bevy::ecs::bundle::Bundle;
 use bevy::ecs::component::Component;
 use bevy::ecs::event::EventReader;
 use bevy::ecs::query::With;
-use bevy::ecs::system::ResMut;
+use bevy::ecs::system::{Local, ResMut};
 use bevy::gizmos::gizmos::Gizmos;
 use bevy::gizmos::GizmoConfig;
-use bevy::log::{error, trace};
+use bevy::log::warn;
 use bevy::math::{Vec2, Vec3};
 use bevy::prelude::SpatialBundle;
 use bevy::reflect::Reflect;
@@ -43,20 +46,15 @@ use components::config::{map_config_resource, ConfigAsset, ConfigComp};
 use components::line::{SplitLine, XYLine, SPLIT_LEN};
 use components::lua::LuaAsset;
 use dsp::audio_graph::AudioControl;
-use dsp::oscillators::Oscillator;
 use dsp::read::Read;
 use instancing::{InstanceData, InstanceMaterialData};
 use post::feedback::FeedbackBundle;
-use rayon::iter::{
-    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
-};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use util::{CRUST, OVERLAY0};
 
 const OSCIL_TARGET: u8 = 1;
 const UI_TARGET: u8 = 0;
 
-const FREQUENCY_TEMP: f32 = 144.0;
-
 #[cfg(debug_assertions)]
 fn main() {
     use std::fs;
@@ -64,6 +62,7 @@ fn main() {
     use bevy::{
         app::{FixedUpdate, PostUpdate},
         ecs::schedule::IntoSystemConfigs,
+        log::LogPlugin,
         render::texture::ImagePlugin,
         time::Fixed,
         window::{PresentMode, Window, WindowPlugin, WindowResolution},
@@ -75,19 +74,35 @@ fn main() {
             config::ConfigLoader,
             grid::system::setup_grid,
             lua::LuaLoader,
+            midi::{
+                record::MidiRecorder,
+                system::{export_midi_keys, play_midi},
+                MidiAsset, MidiLoader,
+            },
             nodes::{
                 blueprints::{init_temp_blueprints, initialize_gen_node},
                 generic::{
-                    system::{spawn_audio_pulses, tick_pulses},
-                    types::AudioNodePulseEvent,
+                    system::{spawn_audio_pulses, spawn_data_pulses, tick_pulses},
+                    types::{AudioNodePulseEvent, DataPulseEvent},
                 },
                 system::keyboard_input_temp,
             },
+            osc::OscPlugin,
+            sample::{SampleAsset, SampleLoader},
+            transport::{advance_transport, transport_playing, Transport, TransportPlugin},
         },
-        dsp::audio_graph::AudioPlugin,
+        dsp::{
+            audio_graph::AudioPlugin,
+            backend::BackendSettings,
+            render::{render_app, RenderSettings},
+        },
+        egui::show_wave_errors,
         instancing::InstanceMaterial2dPlugin,
         post::feedback::FeedbackPlugin,
-        systems::fps::{fps_counter_showhide, fps_text_update_system, setup_fps_counter},
+        systems::fps::{
+            dsp_load_text_update_system, fps_counter_showhide, fps_text_update_system,
+            setup_fps_counter,
+        },
     };
 
     let s = fs::read_to_string("assets/config.toml");
@@ -95,6 +110,19 @@ fn main() {
 
     println!("CONFIG: {:#?}", config);
 
+    // render the patch to a file instead of playing it, without opening a window.
+    if let Some(render) = RenderSettings::from_args(std::env::args()) {
+        let backend = BackendSettings::new(&config, std::env::args());
+
+        render_app(render, backend, config)
+            .add_plugins(LogPlugin::default())
+            .run();
+        return;
+    }
+
+    let transport = Transport::from_config(&config);
+    let tick = Time::<Fixed>::from_duration(transport.tick_duration());
+
     App::new()
         .add_plugins((
             DefaultPlugins
@@ -117,19 +145,32 @@ fn main() {
                     }),
                     ..Default::default()
                 }),
-            FrameTimeDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
             FeedbackPlugin,
             EguiPlugin,
             InstanceMaterial2dPlugin,
-            AudioPlugin,
+            TransportPlugin,
+            OscPlugin,
+            AudioPlugin {
+                render: None,
+                backend: BackendSettings::new(&config, std::env::args()),
+            },
         ))
         .add_event::<AudioNodePulseEvent>()
+        .add_event::<DataPulseEvent>()
         .insert_resource(Msaa::Sample8)
         .insert_resource(config)
+        .insert_resource(transport)
+        .insert_resource(tick)
+        .init_resource::<MidiRecorder>()
         .init_asset::<LuaAsset>()
         .init_asset_loader::<LuaLoader>()
         .init_asset::<ConfigAsset>()
         .init_asset_loader::<ConfigLoader>()
+        .init_asset::<SampleAsset>()
+        .init_asset_loader::<SampleLoader>()
+        .init_asset::<MidiAsset>()
+        .init_asset_loader::<MidiLoader>()
         // setup
         .add_systems(Startup, (setup, setup_fps_counter))
         // temporary setup will be removed in future
@@ -139,22 +180,35 @@ fn main() {
         .add_systems(Startup, init_temp_blueprints)
         .add_systems(Update, initialize_gen_node)
         // temporary system
-        .add_systems(Update, change_frequency)
-        .add_systems(Update, keyboard_input_temp)
+        .add_systems(Update, (keyboard_input_temp, export_midi_keys))
         // main drawing systems
         .add_systems(Update, (plot_out, (oscil, line)).chain())
         .add_systems(PostUpdate, clear_lines)
         // fps counter systems
-        .add_systems(Update, (fps_text_update_system, fps_counter_showhide))
+        .add_systems(
+            Update,
+            (
+                fps_text_update_system,
+                dsp_load_text_update_system,
+                fps_counter_showhide,
+            ),
+        )
         // time update
-        .add_systems(FixedUpdate, tick_pulses)
+        .add_systems(
+            FixedUpdate,
+            (
+                tick_pulses.after(advance_transport),
+                // notes of the tick before the transport counts it.
+                play_midi.before(advance_transport),
+            )
+                .run_if(transport_playing),
+        )
+        // error panel
+        .add_systems(Update, show_wave_errors)
         // update config
         .add_systems(PostUpdate, update_config)
         //events
-        .add_systems(PostUpdate, spawn_audio_pulses)
-        .insert_resource(Time::<Fixed>::from_seconds(
-            (60.0 /* one minute */ / 120.0/* BPM */) / 8.0, /* 8 times per beat */
-        ))
+        .add_systems(PostUpdate, (spawn_audio_pulses, spawn_data_pulses))
         .run()
 }
 
@@ -186,7 +240,6 @@ fn setup(
         Camera2dBundle {
             camera_2d: Camera2d {
                 clear_color: ClearColorConfig::None,
-                ..Default::default()
             },
             camera: Camera {
                 hdr: true,
@@ -210,7 +263,6 @@ fn setup(
         Camera2dBundle {
             camera_2d: Camera2d {
                 clear_color: ClearColorConfig::Custom(CRUST),
-                ..Default::default()
             },
             camera: Camera {
                 hdr: true,
@@ -280,81 +332,50 @@ fn setup(
 //     },));
 // }
 
-fn change_frequency(q_control: Query<&AudioControl<Oscillator>>, time: Res<Time>) {
-    if let Ok(control) = q_control.get_single() {
-        trace!("TEST");
-        trace!("FREQUENCY: {}", control.frequency());
-
-        let exp = time.elapsed_seconds_wrapped().sin();
-        let _frequency_hz = 2.0_f32.powf(exp) * FREQUENCY_TEMP;
-        control.set_frequency(_frequency_hz);
-        control.set_time(time.delta_seconds());
-    }
-}
-
 fn plot_out(
-    q_control: Query<&AudioControl<Read>>,
+    mut q_control: Query<&mut AudioControl<Read>>,
     mut lines: Query<&mut XYLine>,
     mut split_lines: Query<&mut SplitLine>,
+    mut overflows: Local<usize>,
 ) {
-    if let Ok(control) = q_control.get_single() {
-        let last_out = control.last_out();
-
-        if last_out.is_none() {
-            return;
-        }
-
+    if let Ok(mut control) = q_control.get_single_mut() {
         if let (Ok(mut split_line), Ok(mut audio_line)) =
             (split_lines.get_single_mut(), lines.get_single_mut())
         {
-            match &last_out {
-                Some(last_out) => {
-                    let end_idx = match last_out.0 >= (audio_line.buffer[0].len() - 1) {
-                        true => {
-                            error!("BUFFER OVERFLOW");
-                            error!("BUFFER OVERFLOW");
-                            error!("BUFFER OVERFLOW"); // TODO: find a better way to display that this is happening
-                            error!("BUFFER OVERFLOW"); // as its a big fuck up when this happens
-                            error!("BUFFER OVERFLOW");
-                            audio_line.buffer[0].len() - 1
-                        }
-                        false => last_out.0,
-                    };
-
-                    audio_line
-                        .buffer
-                        .par_iter_mut()
-                        .enumerate()
-                        .for_each(|(i, line)| {
-                            (0..end_idx).for_each(|j| {
-                                line[j] = last_out.1[i][j];
-                            });
-                        });
-
-                    audio_line.index = end_idx;
-
-                    split_line
-                        .buffer
-                        .par_iter_mut()
-                        .enumerate()
-                        .for_each(|(i, line)| {
-                            let idx = last_out.0;
-
-                            if idx > SPLIT_LEN {
-                                let idx = idx - SPLIT_LEN;
-                                line.clear();
-                                line.append(&mut last_out.1[i][idx..].to_vec());
-                                return ();
-                            }
-
-                            if line.len() + idx > SPLIT_LEN {
-                                line.drain(0..idx);
-                            }
-
-                            line.append(&mut last_out.1[i].to_vec())
-                        });
-                }
-                None => (),
+            let max = audio_line.buffer[0].len() - 1;
+            let buffer = &mut audio_line.buffer;
+
+            let last_out = control.read_into(max, |i, (l, r)| {
+                buffer[0][i] = l;
+                buffer[1][i] = r;
+            });
+
+            // only report new drops, the counters restart with a rebuilt chain.
+            let dropped = control.overflows();
+            if dropped > *overflows {
+                warn!(
+                    "scope dropped {} samples ({} underflows)",
+                    dropped - *overflows,
+                    control.underflows()
+                );
+            }
+            *overflows = dropped;
+
+            if let Some(end_idx) = last_out {
+                audio_line.index = end_idx;
+
+                // the split line keeps the newest samples, its capacity is reused.
+                split_line
+                    .buffer
+                    .iter_mut()
+                    .zip(audio_line.buffer.iter())
+                    .for_each(|(line, samples)| {
+                        let samples = &samples[end_idx.saturating_sub(SPLIT_LEN)..end_idx];
+                        let overflow = (line.len() + samples.len()).saturating_sub(SPLIT_LEN);
+
+                        line.drain(..overflow);
+                        line.extend_from_slice(samples);
+                    });
             }
         }
     }
@@ -447,14 +468,11 @@ fn update_config(
     mut config: ResMut<ConfigAsset>,
 ) {
     for ev in config_event.read() {
-        match ev {
-            AssetEvent::Modified { id } => {
-                let new_config: &ConfigAsset = config_assets.get(id.clone()).unwrap();
-                map_config_resource(&mut config, &new_config);
+        if let AssetEvent::Modified { id } = ev {
+            let new_config: &ConfigAsset = config_assets.get(*id).unwrap();
+            map_config_resource(&mut config, new_config);
 
-                return;
-            }
-            _ => (),
+            return;
         }
     }
 }
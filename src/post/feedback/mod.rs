@@ -342,7 +342,7 @@ fn prepare_feedback_history_textures(
             texture_descriptor.label = Some("feedback_history_2_texture");
             let history_2_texture = texture_cache.get(&render_device, texture_descriptor);
 
-            let textures = if frame_count.0 % 2 == 0 {
+            let textures = if frame_count.0.is_multiple_of(2) {
                 FeedbackHistoryTextures {
                     write: history_1_texture,
                     read: history_2_texture,
@@ -12,10 +12,18 @@ use bevy::{
     text::{Text, TextSection, TextStyle},
     ui::{
         node_bundles::{NodeBundle, TextBundle},
-        BackgroundColor, PositionType, Style, UiRect, Val, ZIndex,
+        BackgroundColor, FlexDirection, PositionType, Style, UiRect, Val, ZIndex,
     },
 };
 
+use crate::{
+    components::nodes::{generic::GenericNode, types::ParentNode},
+    dsp::audio_graph::AudioLoad,
+};
+
+/// nodes listed under the dsp load, the busiest first.
+const DSP_LOAD_NODES: usize = 8;
+
 /// Marker to find the container entity so we can show/hide the FPS counter
 #[derive(Component)]
 pub struct FpsRoot;
@@ -24,6 +32,10 @@ pub struct FpsRoot;
 #[derive(Component)]
 pub struct FpsText;
 
+/// Marker of the dsp load text below the FPS counter
+#[derive(Component)]
+pub struct DspLoadText;
+
 pub fn setup_fps_counter(mut commands: Commands) {
     // create our UI root node
     // this is the wrapper/container for the text
@@ -48,6 +60,8 @@ pub fn setup_fps_counter(mut commands: Commands) {
                     left: Val::Auto,
                     // give it some padding for readability
                     padding: UiRect::all(Val::Px(4.0)),
+                    // the dsp load goes below the FPS
+                    flex_direction: FlexDirection::Column,
                     ..Default::default()
                 },
                 ..Default::default()
@@ -88,7 +102,42 @@ pub fn setup_fps_counter(mut commands: Commands) {
             },
         ))
         .id();
-    commands.entity(root).push_children(&[text_fps]);
+    let text_dsp = commands
+        .spawn((
+            DspLoadText,
+            TextBundle {
+                // total load, then one line per node
+                text: Text::from_sections([
+                    TextSection {
+                        value: "DSP: ".into(),
+                        style: TextStyle {
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    },
+                    TextSection {
+                        value: " N/A".into(),
+                        style: TextStyle {
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    },
+                    TextSection {
+                        value: "".into(),
+                        style: TextStyle {
+                            font_size: 12.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    },
+                ]),
+                ..Default::default()
+            },
+        ))
+        .id();
+    commands.entity(root).push_children(&[text_fps, text_dsp]);
 }
 
 pub fn fps_text_update_system(
@@ -130,6 +179,68 @@ pub fn fps_text_update_system(
     }
 }
 
+/// Shows the share of the audio callback every node takes, and the blocks it overran.
+/// Shares add up, above 100% the callback can not keep up.
+pub fn dsp_load_text_update_system(
+    loads: Query<(&AudioLoad, &GenericNode)>,
+    mut query: Query<&mut Text, With<DspLoadText>>,
+) {
+    let mut nodes = loads
+        .iter()
+        .map(|(load, gnode)| (load, gnode.get_node()))
+        .collect::<Vec<_>>();
+
+    nodes.sort_by(|(a, _), (b, _)| b.share().total_cmp(&a.share()));
+
+    let total = nodes.iter().map(|(load, _)| load.share()).sum::<f32>();
+    let over_budget = nodes
+        .iter()
+        .map(|(load, _)| load.over_budget())
+        .sum::<usize>();
+
+    for mut text in &mut query {
+        if nodes.is_empty() {
+            text.sections[1].value = " N/A".into();
+            text.sections[1].style.color = Color::WHITE;
+            text.sections[2].value = "".into();
+            continue;
+        }
+
+        text.sections[1].value = format!("{:>4.0}% over {}", total * 100.0, over_budget);
+        text.sections[1].style.color = load_color(total);
+
+        text.sections[2].value = nodes
+            .iter()
+            .take(DSP_LOAD_NODES)
+            .map(|(load, node)| {
+                format!(
+                    "\n{:<12} {:>3},{:>3} {:>7.1}us {:>5.1}% peak {:>5.1}% over {}",
+                    node.name.to_string(),
+                    node.pos.x,
+                    node.pos.y,
+                    load.time() * 1_000_000.0,
+                    load.share() * 100.0,
+                    load.peak() * 100.0,
+                    load.over_budget()
+                )
+            })
+            .collect();
+    }
+}
+
+/// green while there is headroom, red when the budget is about to be used up.
+fn load_color(share: f32) -> Color {
+    if share < 0.5 {
+        Color::rgb(0.0, 1.0, 0.0)
+    } else if share < 0.9 {
+        // Between 50-90%, gradually transition from green to red
+        let t = (share - 0.5) / (0.9 - 0.5);
+        Color::rgb(t, 1.0 - t, 0.0)
+    } else {
+        Color::rgb(1.0, 0.0, 0.0)
+    }
+}
+
 /// Toggle the FPS counter when pressing F12
 pub fn fps_counter_showhide(
     mut q: Query<&mut Visibility, With<FpsRoot>>,